@@ -28,4 +28,11 @@ fn main() {
 
     // Set the linker script to the one provided by cortex-m-rt.
     println!("cargo:rustc-link-arg=-Tlink.x");
+
+    // On Armv6-M, cortex-m-rt's interrupt table only has 32 entries, which
+    // doesn't reach the UART interrupts. Tell the code, so it can poll them.
+    println!("cargo:rustc-check-cfg=cfg(armv6m)");
+    if env::var("TARGET").unwrap().starts_with("thumbv6m-") {
+        println!("cargo:rustc-cfg=armv6m");
+    }
 }
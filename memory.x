@@ -33,4 +33,16 @@ SECTIONS {
         KEEP(*(.disk_image));
         _disk_end = .;
     } > DDR4_SDRAM
+
+    /* We keep the BIOS configuration data block here */
+    .config_store (NOLOAD) : ALIGN(4)
+    {
+        KEEP(*(.config_store));
+    } > DDR4_SDRAM
+
+    /* Buffers which are too big for the BIOS RAM go here */
+    .fpga_sram (NOLOAD) : ALIGN(4)
+    {
+        KEEP(*(.fpga_sram));
+    } > FPGA_SRAM
 } INSERT BEFORE .text;
@@ -0,0 +1,419 @@
+//! Storage for the BIOS and OS configuration.
+//!
+//! We keep a single record in its own section of DDR4 SDRAM, just after the
+//! disk image. QEMU clears that RAM when it starts, but it survives a reset.
+//!
+//! The record looks like this (all integers are little-endian):
+//!
+//! | Offset  | Size | Contents                                |
+//! | ------- | ---- | --------------------------------------- |
+//! | 0       | 4    | Magic number (`NeoB`)                   |
+//! | 4       | 2    | Record version                          |
+//! | 6       | 2    | Length of the BIOS settings (`m`)       |
+//! | 8       | 4    | Length of the OS configuration (`n`)    |
+//! | 12      | 4    | CRC-32 of everything after the header   |
+//! | 16      | n    | The OS configuration (opaque to us)     |
+//! | 16 + n  | m    | The BIOS settings                       |
+//!
+//! New BIOS settings are added on the end, and bump the version. When we
+//! read an older record, any settings it doesn't have get their default
+//! value, and any it has that we've since dropped are skipped. Records from before we had BIOS settings (magic number `NeoC`,
+//! then a `u32` length, a `u32` CRC and the OS configuration) are migrated
+//! too.
+//!
+//! With the `host-config` feature, we also copy the store to and from a file
+//! on the host (using semihosting), so the configuration survives restarting
+//! QEMU.
+
+use neotron_common_bios as common;
+
+use crate::NewlineMode;
+
+/// How much space we have for the configuration, including the header
+const STORE_LEN: usize = 4096;
+
+/// Marks the start of a configuration record
+const MAGIC: u32 = u32::from_le_bytes(*b"NeoB");
+
+/// The record version we write.
+///
+/// * Version 1 had everything up to the newline modes
+/// * Version 2 added the screen mirror port
+/// * Version 3 added the keyboard port
+/// * Version 4 added the debug port
+/// * Version 5 dropped the boot device, which was at the start
+const VERSION: u16 = 5;
+
+/// The size of the record header
+const HEADER_LEN: usize = 16;
+
+/// Marks the start of a record from before we had BIOS settings
+const LEGACY_MAGIC: u32 = u32::from_le_bytes(*b"NeoC");
+
+/// The size of the header on a record from before we had BIOS settings
+const LEGACY_HEADER_LEN: usize = 12;
+
+/// The file on the host we keep the configuration in, with the `host-config`
+/// feature. It is relative to wherever QEMU was started.
+#[cfg(feature = "host-config")]
+const HOST_FILE_PATH: &[u8] = b"neotron-config.bin\0";
+
+/// Where we keep the configuration.
+///
+/// The section is `NOLOAD`, so we don't wipe it when we load the BIOS.
+#[link_section = ".config_store"]
+static mut CONFIG_STORE: [u8; STORE_LEN] = [0; STORE_LEN];
+
+/// Where the BIOS gets the time from at start-up
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ClockSource {
+    /// Trust whatever the RTC says
+    Rtc = 0,
+    /// Ask the host, using semihosting. If the host doesn't answer (because
+    /// QEMU doesn't have the `-semihosting` option), we trust the RTC.
+    Host = 1,
+}
+
+/// The settings which belong to the BIOS, rather than the OS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BiosSettings {
+    /// The serial device the BIOS talks to you on
+    pub console_port: u8,
+    /// The baud rate for the BIOS console
+    pub console_baud: u32,
+    /// Refuse to write to the disk
+    pub disk_write_protect: bool,
+    /// Where we get the time from at start-up
+    pub clock_source: ClockSource,
+    /// How each serial device translates line endings
+    pub newline_modes: [NewlineMode; 7],
+    /// The serial device we mirror the text-mode screen to, if any
+    pub mirror_port: Option<u8>,
+    /// The serial device we decode keyboard events from, if any
+    pub keyboard_port: Option<u8>,
+    /// The serial device we send debug messages to, if any. This is `ser1`
+    /// unless you change it, so there's somewhere to look without going into
+    /// setup.
+    pub debug_port: Option<u8>,
+}
+
+impl BiosSettings {
+    /// How many bytes the settings take up in a record
+    const ENCODED_LEN: usize = 17;
+
+    /// How we store a port of `None`
+    const NO_PORT: u8 = 0xFF;
+
+    /// Convert the settings into bytes for the record.
+    fn encode(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0u8; Self::ENCODED_LEN];
+        bytes[0] = self.console_port;
+        bytes[1..5].copy_from_slice(&self.console_baud.to_le_bytes());
+        bytes[5] = u8::from(self.disk_write_protect);
+        bytes[6] = self.clock_source as u8;
+        for (byte, mode) in bytes[7..14].iter_mut().zip(self.newline_modes.iter()) {
+            *byte = *mode as u8;
+        }
+        bytes[14] = self.mirror_port.unwrap_or(Self::NO_PORT);
+        bytes[15] = self.keyboard_port.unwrap_or(Self::NO_PORT);
+        bytes[16] = self.debug_port.unwrap_or(Self::NO_PORT);
+        bytes
+    }
+
+    /// Convert bytes from a record of the given version into settings.
+    ///
+    /// Anything missing or invalid gets its default value.
+    fn decode(version: u16, bytes: &[u8]) -> BiosSettings {
+        // Before version 5, the boot device came first
+        let bytes = if version < 5 {
+            bytes.get(1..).unwrap_or_default()
+        } else {
+            bytes
+        };
+        let mut settings = BiosSettings::default();
+        match bytes.first() {
+            Some(value) if Self::is_port(*value) => settings.console_port = *value,
+            _ => {}
+        }
+        if let Some(value) = bytes.get(1..5) {
+            settings.console_baud = read_u32(value, 0);
+        }
+        if let Some(value) = bytes.get(5) {
+            settings.disk_write_protect = *value != 0;
+        }
+        match bytes.get(6) {
+            Some(0) => settings.clock_source = ClockSource::Rtc,
+            Some(1) => settings.clock_source = ClockSource::Host,
+            _ => {}
+        }
+        if let Some(values) = bytes.get(7..14) {
+            for (mode, value) in settings.newline_modes.iter_mut().zip(values) {
+                if let Some(new_mode) = NewlineMode::from_u8(*value) {
+                    *mode = new_mode;
+                }
+            }
+        }
+        match bytes.get(14) {
+            Some(&Self::NO_PORT) => settings.mirror_port = None,
+            Some(value) if Self::is_port(*value) => settings.mirror_port = Some(*value),
+            _ => {}
+        }
+        match bytes.get(15) {
+            Some(&Self::NO_PORT) => settings.keyboard_port = None,
+            Some(value) if Self::is_port(*value) => settings.keyboard_port = Some(*value),
+            _ => {}
+        }
+        match bytes.get(16) {
+            Some(&Self::NO_PORT) => settings.debug_port = None,
+            Some(value) if Self::is_port(*value) => settings.debug_port = Some(*value),
+            _ => {}
+        }
+        settings
+    }
+
+    /// Is this one of our serial devices?
+    fn is_port(device: u8) -> bool {
+        usize::from(device) < crate::SERIAL_NAMES.len()
+    }
+}
+
+impl Default for BiosSettings {
+    fn default() -> BiosSettings {
+        BiosSettings {
+            console_port: 0,
+            console_baud: crate::DEFAULT_SERIAL_CONFIG.data_rate_bps,
+            disk_write_protect: false,
+            clock_source: ClockSource::Host,
+            newline_modes: crate::DEFAULT_NEWLINE_MODES,
+            mirror_port: None,
+            keyboard_port: None,
+            debug_port: Some(1),
+        }
+    }
+}
+
+/// What we found in the store at start-up
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadStatus {
+    /// Nothing has been saved
+    Empty,
+    /// We loaded a valid record
+    Loaded,
+    /// We loaded a record from an older BIOS, and updated it
+    Migrated,
+    /// The record was corrupt, so we've gone back to the defaults
+    Corrupt,
+}
+
+impl core::fmt::Display for LoadStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let s = match self {
+            LoadStatus::Empty => "none saved, using defaults",
+            LoadStatus::Loaded => "loaded",
+            LoadStatus::Migrated => "loaded and updated from an older BIOS",
+            LoadStatus::Corrupt => "corrupt! Reset to defaults",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Load the configuration.
+///
+/// Call this once at start-up. Older records are migrated to the current
+/// version, and corrupt records are replaced with the defaults.
+pub fn init() -> (BiosSettings, LoadStatus) {
+    #[cfg(feature = "host-config")]
+    if let Some(mut file) = crate::semihosting::HostFile::open_read(HOST_FILE_PATH) {
+        let store = store_mut();
+        let count = file.read(store);
+        // Don't leave part of an older record lying around
+        store[count..].fill(0);
+    }
+
+    match read_u32(store(), 0) {
+        MAGIC => match parse_record() {
+            Some((version, settings)) if version >= VERSION => (settings, LoadStatus::Loaded),
+            Some((_version, settings)) => {
+                let os_len = os_config().map_or(0, |c| c.len());
+                match write_record(&settings, OsConfig::Kept(os_len)) {
+                    Ok(()) => (settings, LoadStatus::Migrated),
+                    Err(_) => reset(),
+                }
+            }
+            None => reset(),
+        },
+        LEGACY_MAGIC => match migrate_legacy() {
+            Some(settings) => (settings, LoadStatus::Migrated),
+            None => reset(),
+        },
+        _ => (BiosSettings::default(), LoadStatus::Empty),
+    }
+}
+
+/// Get the saved OS configuration.
+///
+/// Returns `None` if the OS hasn't saved one.
+pub fn os_config() -> Option<&'static [u8]> {
+    let store = store();
+    if read_u32(store, 0) != MAGIC {
+        return None;
+    }
+    let os_len = read_u32(store, 8) as usize;
+    if os_len == 0 {
+        return None;
+    }
+    store.get(HEADER_LEN..HEADER_LEN + os_len)
+}
+
+/// Save a new OS configuration, along with the BIOS settings.
+pub fn save_os_config(data: &[u8], settings: &BiosSettings) -> Result<(), common::Error> {
+    write_record(settings, OsConfig::New(data))
+}
+
+/// Save new BIOS settings, keeping the current OS configuration.
+pub fn save_bios_settings(settings: &BiosSettings) -> Result<(), common::Error> {
+    let os_len = os_config().map_or(0, |c| c.len());
+    write_record(settings, OsConfig::Kept(os_len))
+}
+
+/// Check the record in the store, and get the version and BIOS settings
+/// from it.
+fn parse_record() -> Option<(u16, BiosSettings)> {
+    let store = store();
+    let version = read_u16(store, 4);
+    let bios_len = usize::from(read_u16(store, 6));
+    let os_len = read_u32(store, 8) as usize;
+    let crc = read_u32(store, 12);
+    let body = store.get(HEADER_LEN..HEADER_LEN.checked_add(os_len)?.checked_add(bios_len)?)?;
+    if crc32(body) != crc {
+        return None;
+    }
+    Some((version, BiosSettings::decode(version, &body[os_len..])))
+}
+
+/// Convert a record from before we had BIOS settings into a current one.
+fn migrate_legacy() -> Option<BiosSettings> {
+    let store = store_mut();
+    let os_len = read_u32(store, 4) as usize;
+    let crc = read_u32(store, 8);
+    let os_config = store.get(LEGACY_HEADER_LEN..LEGACY_HEADER_LEN.checked_add(os_len)?)?;
+    if crc32(os_config) != crc {
+        return None;
+    }
+    let settings = BiosSettings::default();
+    write_record(&settings, OsConfig::Legacy(os_len)).ok()?;
+    Some(settings)
+}
+
+/// Replace whatever is in the store with the default settings.
+fn reset() -> (BiosSettings, LoadStatus) {
+    let settings = BiosSettings::default();
+    // If even this fails, the next boot will find the corrupt record again
+    let _ = write_record(&settings, OsConfig::Kept(0));
+    (settings, LoadStatus::Corrupt)
+}
+
+/// Where the OS configuration in a new record comes from
+#[derive(Debug, Copy, Clone)]
+enum OsConfig<'a> {
+    /// The given number of bytes, already in the right place in the store
+    Kept(usize),
+    /// The given number of bytes, in the store after a legacy header
+    Legacy(usize),
+    /// Some new bytes
+    New(&'a [u8]),
+}
+
+/// Write a new record, with the given BIOS settings and OS configuration.
+///
+/// With the `host-config` feature, we write the host file first, and only
+/// change the store if that worked, so the two always agree.
+fn write_record(settings: &BiosSettings, os_config: OsConfig) -> Result<(), common::Error> {
+    let (os_start, os_len) = match os_config {
+        OsConfig::Kept(len) => (HEADER_LEN, len),
+        OsConfig::Legacy(len) => (LEGACY_HEADER_LEN, len),
+        OsConfig::New(data) => (0, data.len()),
+    };
+    let bios_start = HEADER_LEN + os_len;
+    let bios_end = bios_start + BiosSettings::ENCODED_LEN;
+    if bios_end > STORE_LEN {
+        return Err(common::Error::UnsupportedConfiguration(0));
+    }
+    let os_data = match os_config {
+        OsConfig::New(data) => data,
+        _ => &store()[os_start..os_start + os_len],
+    };
+    let bios_data = settings.encode();
+    let crc = crc32_continue(crc32(os_data), &bios_data);
+    let mut header = [0u8; HEADER_LEN];
+    header[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+    header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+    header[6..8].copy_from_slice(&(BiosSettings::ENCODED_LEN as u16).to_le_bytes());
+    header[8..12].copy_from_slice(&(os_len as u32).to_le_bytes());
+    header[12..16].copy_from_slice(&crc.to_le_bytes());
+    #[cfg(feature = "host-config")]
+    {
+        let saved = crate::semihosting::HostFile::create(HOST_FILE_PATH)
+            .map(|mut file| {
+                file.write_all(&header) && file.write_all(os_data) && file.write_all(&bios_data)
+            })
+            .unwrap_or(false);
+        if !saved {
+            return Err(common::Error::DeviceError(0));
+        }
+    }
+    let store = store_mut();
+    match os_config {
+        OsConfig::New(data) => store[HEADER_LEN..bios_start].copy_from_slice(data),
+        _ => store.copy_within(os_start..os_start + os_len, HEADER_LEN),
+    }
+    store[bios_start..bios_end].copy_from_slice(&bios_data);
+    store[..HEADER_LEN].copy_from_slice(&header);
+    Ok(())
+}
+
+/// Look at the store.
+fn store() -> &'static [u8; STORE_LEN] {
+    unsafe { &*core::ptr::addr_of!(CONFIG_STORE) }
+}
+
+/// Change the store.
+///
+/// Only the OS thread touches the configuration, so we can't have two of
+/// these at once.
+fn store_mut() -> &'static mut [u8; STORE_LEN] {
+    unsafe { &mut *core::ptr::addr_of_mut!(CONFIG_STORE) }
+}
+
+/// Read a little-endian `u16` from the given offset.
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    let mut word = [0u8; 2];
+    word.copy_from_slice(&bytes[offset..offset + 2]);
+    u16::from_le_bytes(word)
+}
+
+/// Read a little-endian `u32` from the given offset.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0u8; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(word)
+}
+
+/// Calculate the CRC-32 (as used by Ethernet and zip) of some bytes.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_continue(0, data)
+}
+
+/// Carry on calculating a CRC-32, given the CRC of the bytes before `data`.
+fn crc32_continue(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,275 @@
+//! An 8x8 bitmap font, for drawing text cells as pixels.
+//!
+//! This has all 256 glyphs of Code Page 850, the same as
+//! `video::glyph_to_char`. The printable ASCII characters are from the public
+//! domain `font8x8_basic`, and the rest were drawn to match them. Control
+//! characters get the symbols the IBM PC shows for them.
+//!
+//! In each row, bit 0 is the left-most pixel.
+
+/// Get one row of pixels from a glyph.
+///
+/// `row` counts from the top, and must be less than 8.
+pub fn glyph_row(glyph: u8, row: usize) -> u8 {
+    FONT[usize::from(glyph)][row]
+}
+
+/// The glyphs, in Code Page 850 order
+static FONT: [[u8; 8]; 256] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0x00 NUL
+    [0x7E, 0x81, 0xA5, 0x81, 0xBD, 0x99, 0x81, 0x7E], // 0x01 ☺
+    [0x7E, 0xFF, 0xDB, 0xFF, 0xC3, 0xE7, 0xFF, 0x7E], // 0x02 ☻
+    [0x36, 0x7F, 0x7F, 0x7F, 0x3E, 0x1C, 0x08, 0x00], // 0x03 ♥
+    [0x08, 0x1C, 0x3E, 0x7F, 0x3E, 0x1C, 0x08, 0x00], // 0x04 ♦
+    [0x1C, 0x3E, 0x1C, 0x7F, 0x7F, 0x08, 0x1C, 0x00], // 0x05 ♣
+    [0x08, 0x1C, 0x3E, 0x7F, 0x7F, 0x08, 0x1C, 0x00], // 0x06 ♠
+    [0x00, 0x00, 0x18, 0x3C, 0x3C, 0x18, 0x00, 0x00], // 0x07 •
+    [0xFF, 0xFF, 0xE7, 0xC3, 0xC3, 0xE7, 0xFF, 0xFF], // 0x08 ◘
+    [0x00, 0x3C, 0x66, 0x42, 0x42, 0x66, 0x3C, 0x00], // 0x09 ○
+    [0xFF, 0xC3, 0x99, 0xBD, 0xBD, 0x99, 0xC3, 0xFF], // 0x0A ◙
+    [0xF0, 0xE0, 0xB0, 0x3C, 0x66, 0x66, 0x3C, 0x00], // 0x0B ♂
+    [0x3C, 0x66, 0x66, 0x3C, 0x18, 0x7E, 0x18, 0x00], // 0x0C ♀
+    [0x78, 0x48, 0x78, 0x08, 0x08, 0x0E, 0x0F, 0x06], // 0x0D ♪
+    [0xFE, 0x82, 0xFE, 0x82, 0xC2, 0xE3, 0x63, 0x00], // 0x0E ♫
+    [0x99, 0x5A, 0x3C, 0xE7, 0xE7, 0x3C, 0x5A, 0x99], // 0x0F ☼
+    [0x01, 0x07, 0x1F, 0x7F, 0x1F, 0x07, 0x01, 0x00], // 0x10 ►
+    [0x40, 0x70, 0x7C, 0x7F, 0x7C, 0x70, 0x40, 0x00], // 0x11 ◄
+    [0x08, 0x1C, 0x2A, 0x08, 0x2A, 0x1C, 0x08, 0x00], // 0x12 ↕
+    [0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x66, 0x00], // 0x13 ‼
+    [0xFE, 0xDB, 0xDB, 0xDE, 0xD8, 0xD8, 0xD8, 0x00], // 0x14 ¶
+    [0x3C, 0x06, 0x1C, 0x36, 0x1C, 0x30, 0x1E, 0x00], // 0x15 §
+    [0x00, 0x00, 0x00, 0x00, 0x7F, 0x7F, 0x7F, 0x00], // 0x16 ▬
+    [0x08, 0x1C, 0x2A, 0x08, 0x2A, 0x1C, 0x08, 0x7F], // 0x17 ↨
+    [0x08, 0x1C, 0x2A, 0x08, 0x08, 0x08, 0x08, 0x00], // 0x18 ↑
+    [0x08, 0x08, 0x08, 0x08, 0x2A, 0x1C, 0x08, 0x00], // 0x19 ↓
+    [0x00, 0x10, 0x20, 0x7F, 0x20, 0x10, 0x00, 0x00], // 0x1A →
+    [0x00, 0x04, 0x02, 0x7F, 0x02, 0x04, 0x00, 0x00], // 0x1B ←
+    [0x00, 0x00, 0x01, 0x01, 0x01, 0x7F, 0x00, 0x00], // 0x1C ∟
+    [0x00, 0x14, 0x22, 0x7F, 0x22, 0x14, 0x00, 0x00], // 0x1D ↔
+    [0x00, 0x08, 0x1C, 0x3E, 0x7F, 0x7F, 0x00, 0x00], // 0x1E ▲
+    [0x00, 0x7F, 0x7F, 0x3E, 0x1C, 0x08, 0x00, 0x00], // 0x1F ▼
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0x20 space
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // 0x21 !
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0x22 "
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // 0x23 #
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // 0x24 $
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // 0x25 %
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // 0x26 &
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // 0x27 '
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // 0x28 (
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // 0x29 )
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // 0x2A *
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // 0x2B +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // 0x2C ,
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // 0x2D -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // 0x2E .
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // 0x2F /
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // 0x30 0
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // 0x31 1
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // 0x32 2
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // 0x33 3
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // 0x34 4
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // 0x35 5
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // 0x36 6
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // 0x37 7
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // 0x38 8
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // 0x39 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // 0x3A :
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // 0x3B ;
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // 0x3C <
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // 0x3D =
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // 0x3E >
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // 0x3F ?
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // 0x40 @
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // 0x41 A
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // 0x42 B
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // 0x43 C
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // 0x44 D
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // 0x45 E
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // 0x46 F
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // 0x47 G
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // 0x48 H
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0x49 I
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // 0x4A J
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // 0x4B K
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // 0x4C L
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // 0x4D M
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // 0x4E N
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // 0x4F O
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // 0x50 P
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // 0x51 Q
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // 0x52 R
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // 0x53 S
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0x54 T
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // 0x55 U
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 0x56 V
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // 0x57 W
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // 0x58 X
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // 0x59 Y
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // 0x5A Z
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // 0x5B [
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // 0x5C \
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // 0x5D ]
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // 0x5E ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // 0x5F _
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // 0x60 `
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 0x61 a
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // 0x62 b
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // 0x63 c
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // 0x64 d
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 0x65 e
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // 0x66 f
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 0x67 g
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // 0x68 h
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0x69 i
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // 0x6A j
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // 0x6B k
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0x6C l
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // 0x6D m
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // 0x6E n
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 0x6F o
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // 0x70 p
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // 0x71 q
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // 0x72 r
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // 0x73 s
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // 0x74 t
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 0x75 u
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 0x76 v
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // 0x77 w
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // 0x78 x
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 0x79 y
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // 0x7A z
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // 0x7B {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // 0x7C |
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // 0x7D }
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0x7E ~
+    [0x08, 0x1C, 0x36, 0x63, 0x63, 0x7F, 0x00, 0x00], // 0x7F ⌂
+    [0x3C, 0x66, 0x03, 0x03, 0x66, 0x3C, 0x18, 0x0C], // 0x80 Ç
+    [0x33, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 0x81 ü
+    [0x38, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 0x82 é
+    [0x1E, 0x33, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 0x83 â
+    [0x33, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 0x84 ä
+    [0x07, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 0x85 à
+    [0x0C, 0x0C, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 0x86 å
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x0C], // 0x87 ç
+    [0x1E, 0x33, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 0x88 ê
+    [0x33, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 0x89 ë
+    [0x07, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 0x8A è
+    [0x33, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0x8B ï
+    [0x1E, 0x33, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0x8C î
+    [0x07, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0x8D ì
+    [0x33, 0x00, 0x0C, 0x1E, 0x33, 0x3F, 0x33, 0x33], // 0x8E Ä
+    [0x0C, 0x0C, 0x0C, 0x1E, 0x33, 0x3F, 0x33, 0x33], // 0x8F Å
+    [0x38, 0x00, 0x7F, 0x16, 0x1E, 0x16, 0x46, 0x7F], // 0x90 É
+    [0x00, 0x00, 0x36, 0x48, 0x7E, 0x09, 0x76, 0x00], // 0x91 æ
+    [0x7E, 0x09, 0x09, 0x3F, 0x09, 0x09, 0x79, 0x00], // 0x92 Æ
+    [0x1E, 0x33, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 0x93 ô
+    [0x33, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 0x94 ö
+    [0x07, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 0x95 ò
+    [0x1E, 0x33, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 0x96 û
+    [0x07, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 0x97 ù
+    [0x33, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 0x98 ÿ
+    [0x33, 0x00, 0x1C, 0x36, 0x63, 0x63, 0x36, 0x1C], // 0x99 Ö
+    [0x33, 0x00, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F], // 0x9A Ü
+    [0x00, 0x40, 0x3E, 0x33, 0x3B, 0x37, 0x1E, 0x01], // 0x9B ø
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x66, 0x3F, 0x00], // 0x9C £
+    [0x5C, 0x76, 0x63, 0x73, 0x6B, 0x36, 0x1E, 0x01], // 0x9D Ø
+    [0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00, 0x00], // 0x9E ×
+    [0x70, 0x18, 0x18, 0x7E, 0x18, 0x18, 0x0C, 0x07], // 0x9F ƒ
+    [0x38, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 0xA0 á
+    [0x38, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0xA1 í
+    [0x38, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 0xA2 ó
+    [0x38, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 0xA3 ú
+    [0x6E, 0x3B, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // 0xA4 ñ
+    [0x6E, 0x3B, 0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63], // 0xA5 Ñ
+    [0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00, 0x3F, 0x00], // 0xA6 ª
+    [0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00, 0x3F, 0x00], // 0xA7 º
+    [0x0C, 0x00, 0x0C, 0x06, 0x03, 0x33, 0x1E, 0x00], // 0xA8 ¿
+    [0x3E, 0x41, 0x4D, 0x55, 0x4D, 0x55, 0x3E, 0x00], // 0xA9 ®
+    [0x00, 0x00, 0x00, 0x3F, 0x30, 0x30, 0x00, 0x00], // 0xAA ¬
+    [0x42, 0x23, 0x12, 0x68, 0x44, 0x22, 0x71, 0x00], // 0xAB ½
+    [0x42, 0x23, 0x12, 0x28, 0x34, 0x7A, 0x21, 0x00], // 0xAC ¼
+    [0x18, 0x00, 0x18, 0x18, 0x3C, 0x3C, 0x18, 0x00], // 0xAD ¡
+    [0x00, 0x6C, 0x36, 0x1B, 0x36, 0x6C, 0x00, 0x00], // 0xAE «
+    [0x00, 0x1B, 0x36, 0x6C, 0x36, 0x1B, 0x00, 0x00], // 0xAF »
+    [0x11, 0x44, 0x11, 0x44, 0x11, 0x44, 0x11, 0x44], // 0xB0 ░
+    [0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA], // 0xB1 ▒
+    [0xEE, 0xBB, 0xEE, 0xBB, 0xEE, 0xBB, 0xEE, 0xBB], // 0xB2 ▓
+    [0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08], // 0xB3 │
+    [0x08, 0x08, 0x08, 0x0F, 0x08, 0x08, 0x08, 0x08], // 0xB4 ┤
+    [0x38, 0x00, 0x0C, 0x1E, 0x33, 0x3F, 0x33, 0x33], // 0xB5 Á
+    [0x1E, 0x33, 0x0C, 0x1E, 0x33, 0x3F, 0x33, 0x33], // 0xB6 Â
+    [0x07, 0x00, 0x0C, 0x1E, 0x33, 0x3F, 0x33, 0x33], // 0xB7 À
+    [0x3E, 0x41, 0x59, 0x45, 0x59, 0x41, 0x3E, 0x00], // 0xB8 ©
+    [0x14, 0x14, 0x17, 0x10, 0x17, 0x14, 0x14, 0x14], // 0xB9 ╣
+    [0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14], // 0xBA ║
+    [0x00, 0x00, 0x1F, 0x10, 0x17, 0x14, 0x14, 0x14], // 0xBB ╗
+    [0x14, 0x14, 0x17, 0x10, 0x1F, 0x00, 0x00, 0x00], // 0xBC ╝
+    [0x18, 0x7E, 0x1B, 0x1B, 0x1B, 0x7E, 0x18, 0x00], // 0xBD ¢
+    [0x33, 0x33, 0x1E, 0x3F, 0x0C, 0x3F, 0x0C, 0x00], // 0xBE ¥
+    [0x00, 0x00, 0x00, 0x0F, 0x08, 0x08, 0x08, 0x08], // 0xBF ┐
+    [0x08, 0x08, 0x08, 0xF8, 0x00, 0x00, 0x00, 0x00], // 0xC0 └
+    [0x08, 0x08, 0x08, 0xFF, 0x00, 0x00, 0x00, 0x00], // 0xC1 ┴
+    [0x00, 0x00, 0x00, 0xFF, 0x08, 0x08, 0x08, 0x08], // 0xC2 ┬
+    [0x08, 0x08, 0x08, 0xF8, 0x08, 0x08, 0x08, 0x08], // 0xC3 ├
+    [0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00], // 0xC4 ─
+    [0x08, 0x08, 0x08, 0xFF, 0x08, 0x08, 0x08, 0x08], // 0xC5 ┼
+    [0x6E, 0x3B, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 0xC6 ã
+    [0x6E, 0x3B, 0x0C, 0x1E, 0x33, 0x3F, 0x33, 0x33], // 0xC7 Ã
+    [0x14, 0x14, 0xF4, 0x04, 0xFC, 0x00, 0x00, 0x00], // 0xC8 ╚
+    [0x00, 0x00, 0xFC, 0x04, 0xF4, 0x14, 0x14, 0x14], // 0xC9 ╔
+    [0x14, 0x14, 0xF7, 0x00, 0xFF, 0x00, 0x00, 0x00], // 0xCA ╩
+    [0x00, 0x00, 0xFF, 0x00, 0xF7, 0x14, 0x14, 0x14], // 0xCB ╦
+    [0x14, 0x14, 0xF4, 0x04, 0xF4, 0x14, 0x14, 0x14], // 0xCC ╠
+    [0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x00], // 0xCD ═
+    [0x14, 0x14, 0xF7, 0x00, 0xF7, 0x14, 0x14, 0x14], // 0xCE ╬
+    [0x00, 0x41, 0x3E, 0x22, 0x22, 0x3E, 0x41, 0x00], // 0xCF ¤
+    [0x2C, 0x18, 0x34, 0x3E, 0x33, 0x33, 0x1E, 0x00], // 0xD0 ð
+    [0x1F, 0x36, 0x66, 0x6F, 0x66, 0x36, 0x1F, 0x00], // 0xD1 Ð
+    [0x1E, 0x33, 0x7F, 0x16, 0x1E, 0x16, 0x46, 0x7F], // 0xD2 Ê
+    [0x33, 0x00, 0x7F, 0x16, 0x1E, 0x16, 0x46, 0x7F], // 0xD3 Ë
+    [0x07, 0x00, 0x7F, 0x16, 0x1E, 0x16, 0x46, 0x7F], // 0xD4 È
+    [0x00, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 0xD5 ı
+    [0x38, 0x00, 0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E], // 0xD6 Í
+    [0x1E, 0x33, 0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E], // 0xD7 Î
+    [0x33, 0x00, 0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E], // 0xD8 Ï
+    [0x08, 0x08, 0x08, 0x0F, 0x00, 0x00, 0x00, 0x00], // 0xD9 ┘
+    [0x00, 0x00, 0x00, 0xF8, 0x08, 0x08, 0x08, 0x08], // 0xDA ┌
+    [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], // 0xDB █
+    [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF], // 0xDC ▄
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // 0xDD ¦
+    [0x07, 0x00, 0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E], // 0xDE Ì
+    [0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00], // 0xDF ▀
+    [0x38, 0x00, 0x1C, 0x36, 0x63, 0x63, 0x36, 0x1C], // 0xE0 Ó
+    [0x1E, 0x33, 0x33, 0x1B, 0x33, 0x33, 0x1B, 0x03], // 0xE1 ß
+    [0x1E, 0x33, 0x1C, 0x36, 0x63, 0x63, 0x36, 0x1C], // 0xE2 Ô
+    [0x07, 0x00, 0x1C, 0x36, 0x63, 0x63, 0x36, 0x1C], // 0xE3 Ò
+    [0x6E, 0x3B, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 0xE4 õ
+    [0x6E, 0x3B, 0x1C, 0x36, 0x63, 0x63, 0x36, 0x1C], // 0xE5 Õ
+    [0x00, 0x00, 0x66, 0x66, 0x66, 0x3E, 0x06, 0x03], // 0xE6 µ
+    [0x07, 0x06, 0x3E, 0x66, 0x66, 0x3E, 0x06, 0x0F], // 0xE7 þ
+    [0x0F, 0x06, 0x3E, 0x66, 0x3E, 0x06, 0x0F, 0x00], // 0xE8 Þ
+    [0x38, 0x00, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F], // 0xE9 Ú
+    [0x1E, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F], // 0xEA Û
+    [0x07, 0x00, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F], // 0xEB Ù
+    [0x38, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 0xEC ý
+    [0x38, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x1E], // 0xED Ý
+    [0x3F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0xEE ¯
+    [0x30, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0xEF ´
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // 0xF0 soft hyphen
+    [0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x3F, 0x00], // 0xF1 ±
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0xFF], // 0xF2 ‗
+    [0x47, 0x24, 0x16, 0x2C, 0x37, 0x7A, 0x21, 0x00], // 0xF3 ¾
+    [0xFE, 0xDB, 0xDB, 0xDE, 0xD8, 0xD8, 0xD8, 0x00], // 0xF4 ¶
+    [0x3C, 0x06, 0x1C, 0x36, 0x1C, 0x30, 0x1E, 0x00], // 0xF5 §
+    [0x00, 0x0C, 0x00, 0x3F, 0x00, 0x0C, 0x00, 0x00], // 0xF6 ÷
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x0E], // 0xF7 ¸
+    [0x0E, 0x1B, 0x1B, 0x0E, 0x00, 0x00, 0x00, 0x00], // 0xF8 °
+    [0x33, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0xF9 ¨
+    [0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00, 0x00, 0x00], // 0xFA ·
+    [0x04, 0x06, 0x04, 0x04, 0x0E, 0x00, 0x00, 0x00], // 0xFB ¹
+    [0x07, 0x08, 0x06, 0x08, 0x07, 0x00, 0x00, 0x00], // 0xFC ³
+    [0x06, 0x09, 0x04, 0x02, 0x0F, 0x00, 0x00, 0x00], // 0xFD ²
+    [0x00, 0x00, 0x3C, 0x3C, 0x3C, 0x3C, 0x00, 0x00], // 0xFE ■
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0xFF no-break space
+];
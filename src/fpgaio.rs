@@ -0,0 +1,188 @@
+//! A driver for the MPS3 FPGA I/O block.
+//!
+//! This has the user push-buttons and DIP switches. We don't get interrupts
+//! from them, so we poll them, and remember which ones have changed.
+//!
+//! There are no key codes for them, and borrowing the codes of real keys
+//! would mean the OS couldn't tell a button from a key, so they aren't
+//! keyboard events. Instead, they look like a read-only device on an I2C bus
+//! of their own (see `i2c_write_read`), a bit like a GPIO expander. Its
+//! registers are:
+//!
+//! * 0: the buttons that are pressed, one bit each, from button 0 up
+//! * 1: the switches that are on, one bit each, from switch 0 up
+//! * 2: the buttons that have changed since this register was last read
+//! * 3: the switches that have changed since this register was last read
+//!
+//! Write the register number, then read as many bytes as you like; the
+//! register number goes up by one for each byte, and registers past the end
+//! read as zero. Reading a register with no register number reads from
+//! register 0.
+//!
+//! It also has the user LEDs. The first three show the keyboard's lock keys,
+//! and the rest are ours.
+
+#[cfg(feature = "script")]
+use core::cell::Cell;
+
+/// How many push-buttons there are
+pub const BUTTONS: usize = 2;
+
+/// How many switches there are
+pub const SWITCHES: usize = 8;
+
+/// The address of the buttons and switches on their I2C bus
+pub const I2C_ADDRESS: u8 = 0x20;
+
+/// The register with the buttons that are pressed
+const REG_BUTTONS: usize = 0;
+
+/// The register with the switches that are on
+const REG_SWITCHES: usize = 1;
+
+/// The register with the buttons that have changed
+const REG_BUTTONS_CHANGED: usize = 2;
+
+/// The register with the switches that have changed
+const REG_SWITCHES_CHANGED: usize = 3;
+
+/// The buttons (first) and switches (second) the script is holding down or
+/// turning on, and the ones it has changed since we last polled.
+///
+/// We keep the changes, so a press and release between two polls isn't
+/// lost.
+#[cfg(feature = "script")]
+static SCRIPTED: critical_section::Mutex<Cell<([u8; 2], [u8; 2])>> =
+    critical_section::Mutex::new(Cell::new(([0; 2], [0; 2])));
+
+/// The LED showing Num Lock
+pub const LED_NUM_LOCK: u32 = 1 << 0;
+
+/// The LED showing Caps Lock
+pub const LED_CAPS_LOCK: u32 = 1 << 1;
+
+/// The LED showing Scroll Lock
+pub const LED_SCROLL_LOCK: u32 = 1 << 2;
+
+/// The LED that is lit while we're reading or writing the disk
+pub const LED_DISK: u32 = 1 << 3;
+
+/// The FPGA I/O block, and what we last saw on its inputs.
+pub struct FpgaIo<const ADDR: usize> {
+    /// The buttons (first) and switches (second) we last saw pressed or on,
+    /// one bit each
+    inputs: [u8; 2],
+    /// The bits of `inputs` that have changed since the OS last read them
+    changed: [u8; 2],
+}
+
+impl<const ADDR: usize> FpgaIo<ADDR> {
+    /// Create a handle to the FPGA I/O block at `ADDR`.
+    ///
+    /// We start off thinking everything is released and off, so anything
+    /// held down or on at boot shows up as a change the first time we poll.
+    pub const fn new() -> FpgaIo<ADDR> {
+        FpgaIo {
+            inputs: [0; 2],
+            changed: [0; 2],
+        }
+    }
+
+    /// Look for changes on the buttons and switches.
+    pub fn poll(&mut self) {
+        #[cfg_attr(not(feature = "script"), allow(unused_mut))]
+        let mut inputs = [
+            (self.get_buttons() & ((1 << BUTTONS) - 1)) as u8,
+            (self.get_switches() & ((1 << SWITCHES) - 1)) as u8,
+        ];
+        #[cfg(feature = "script")]
+        critical_section::with(|cs| {
+            let (scripted, changed) = SCRIPTED.borrow(cs).get();
+            for (input, scripted) in inputs.iter_mut().zip(scripted) {
+                *input |= scripted;
+            }
+            for (ours, changed) in self.changed.iter_mut().zip(changed) {
+                *ours |= changed;
+            }
+            SCRIPTED.borrow(cs).set((scripted, [0; 2]));
+        });
+        for ((changed, old), new) in self.changed.iter_mut().zip(self.inputs).zip(inputs) {
+            *changed |= old ^ new;
+        }
+        self.inputs = inputs;
+    }
+
+    /// Read our registers into `buffer`, starting at `register`.
+    ///
+    /// Reading one of the registers of changes clears it.
+    pub fn read_registers(&mut self, register: usize, buffer: &mut [u8]) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = match register + offset {
+                REG_BUTTONS => self.inputs[0],
+                REG_SWITCHES => self.inputs[1],
+                REG_BUTTONS_CHANGED => core::mem::take(&mut self.changed[0]),
+                REG_SWITCHES_CHANGED => core::mem::take(&mut self.changed[1]),
+                _ => 0,
+            };
+        }
+    }
+
+    /// Get which LEDs are lit, one bit each.
+    pub fn leds(&self) -> u32 {
+        let ptr = ADDR as *mut u32;
+        unsafe { ptr.read_volatile() }
+    }
+
+    /// Light the LEDs in `mask` whose bits are set in `value`, and turn off
+    /// the others in `mask`.
+    pub fn set_leds(&mut self, mask: u32, value: u32) {
+        let leds = (self.leds() & !mask) | (value & mask);
+        let ptr = ADDR as *mut u32;
+        unsafe { ptr.write_volatile(leds) }
+    }
+
+    /// Read the button register (one bit per button, set when pressed)
+    fn get_buttons(&self) -> u32 {
+        let ptr = (ADDR + 0x08) as *mut u32;
+        unsafe { ptr.read_volatile() }
+    }
+
+    /// Read the switch register (one bit per switch, set when on)
+    fn get_switches(&self) -> u32 {
+        let ptr = (ADDR + 0x28) as *mut u32;
+        unsafe { ptr.read_volatile() }
+    }
+}
+
+/// Press (`true`) or release (`false`) a push-button for the script.
+///
+/// The button stays pressed until the script releases it, whatever the real
+/// one is doing.
+#[cfg(feature = "script")]
+pub fn script_button(button: usize, pressed: bool) {
+    script_input(0, button, pressed);
+}
+
+/// Turn a switch on (`true`) or off (`false`) for the script.
+///
+/// The switch stays on until the script turns it off, whatever the real one
+/// is doing.
+#[cfg(feature = "script")]
+pub fn script_switch(switch: usize, on: bool) {
+    script_input(1, switch, on);
+}
+
+/// Set bit `bit` of the scripted inputs in `bank` to `on`, and remember that
+/// it changed if it did.
+#[cfg(feature = "script")]
+fn script_input(bank: usize, bit: usize, on: bool) {
+    critical_section::with(|cs| {
+        let (mut scripted, mut changed) = SCRIPTED.borrow(cs).get();
+        let mask = 1 << bit;
+        if ((scripted[bank] & mask) != 0) != on {
+            scripted[bank] ^= mask;
+            changed[bank] |= mask;
+        }
+        SCRIPTED.borrow(cs).set((scripted, changed));
+    });
+}
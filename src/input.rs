@@ -0,0 +1,544 @@
+//! Turns bytes from a serial port into HID events.
+//!
+//! A terminal sends characters, not key presses, so we work backwards: `A`
+//! becomes Left Shift and A being pressed and released, Ctrl-C becomes Left
+//! Control and C, and so on, as if you had a US-English keyboard. We also
+//! decode the VT100/xterm escape sequences for the cursor keys, Home, End,
+//! Insert, Delete, Page Up, Page Down and the function keys, including any
+//! Shift, Alt or Ctrl modifiers they carry. Esc followed by a character is
+//! Alt and that character. An Esc on its own is the Escape key, once we've
+//! waited long enough to be sure nothing follows it.
+//!
+//! Terminals don't tell us when a key is released, so every key press is
+//! immediately followed by its release.
+//!
+//! We also ask the terminal to report what the mouse is doing (using xterm's
+//! SGR mouse reporting), and turn that into mouse events. The terminal tells
+//! us which cell the mouse is over, so we put the pointer in the middle of
+//! that cell on our screen, and report how far it moved in pixels.
+//!
+//! Anything the script (see the `script` module, with the `script` feature)
+//! types goes in the same queue.
+
+use neotron_common_bios as common;
+
+use common::hid::KeyCode;
+
+use core::fmt::Write;
+
+#[cfg(feature = "script")]
+use crate::script;
+use crate::{mutex::NeoMutex, ring::RingBuffer, timer, video, SerialPort, SerialWriter};
+
+/// The byte that starts an escape sequence
+const ESC: u8 = 0x1B;
+
+/// How long we wait after an Esc for the rest of an escape sequence
+const ESCAPE_TIMEOUT_US: u64 = 50_000;
+
+/// Modifier bits, numbered as in xterm's modifier parameter (minus one)
+const SHIFT: u8 = 1 << 0;
+const ALT: u8 = 1 << 1;
+const CTRL: u8 = 1 << 2;
+
+/// The modifier keys we press for each modifier bit
+const MODIFIER_KEYS: [(u8, KeyCode); 3] = [
+    (SHIFT, KeyCode::LShift),
+    (CTRL, KeyCode::LControl),
+    (ALT, KeyCode::LAlt),
+];
+
+/// Turns on xterm's any-event mouse tracking, with SGR-style reports
+const ENABLE_MOUSE_REPORTING: &str = "\x1b[?1003h\x1b[?1006h";
+
+/// Turns off what `ENABLE_MOUSE_REPORTING` turned on
+const DISABLE_MOUSE_REPORTING: &str = "\x1b[?1003l\x1b[?1006l";
+
+/// The bit for the left mouse button, as a PS/2 mouse numbers them
+const MOUSE_LEFT: u8 = 1 << 0;
+
+/// The bit for the right mouse button, as a PS/2 mouse numbers them
+const MOUSE_RIGHT: u8 = 1 << 1;
+
+/// The bit for the middle mouse button, as a PS/2 mouse numbers them
+const MOUSE_MIDDLE: u8 = 1 << 2;
+
+/// The most events one byte can produce (three modifiers, pressed and
+/// released, and the key, pressed and released)
+const MAX_EVENTS_PER_BYTE: usize = 8;
+
+/// How many events we can queue up
+const QUEUE_LEN: usize = 64;
+
+/// Something that happened on one of our input devices
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A key was pressed
+    KeyPress(KeyCode),
+    /// A key was released
+    KeyRelease(KeyCode),
+    /// The mouse moved (by the given number of pixels, with positive `y`
+    /// being down the screen), or a button was pressed or released
+    Mouse { x: i16, y: i16, buttons: u8 },
+}
+
+impl From<Event> for common::hid::HidEvent {
+    fn from(event: Event) -> common::hid::HidEvent {
+        match event {
+            Event::KeyPress(code) => common::hid::HidEvent::KeyPress(code),
+            Event::KeyRelease(code) => common::hid::HidEvent::KeyRelease(code),
+            Event::Mouse { x, y, buttons } => {
+                common::hid::HidEvent::MouseInput(common::hid::MouseData {
+                    x,
+                    y,
+                    buttons: common::hid::MouseButtons::from_bits(buttons),
+                })
+            }
+        }
+    }
+}
+
+/// Our state
+static DECODER: NeoMutex<Decoder> = NeoMutex::new(Decoder {
+    state: ParseState::Ground,
+    queue: RingBuffer::new(Event::KeyRelease(KeyCode::Escape)),
+    mouse_reporting: false,
+    pointer: (0, 0),
+    buttons: 0,
+});
+
+/// Where we are in an escape sequence
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ParseState {
+    /// Not in an escape sequence
+    Ground,
+    /// We've had an Esc, at the given time, and are waiting to see what
+    /// comes next
+    Escape { since_us: u64 },
+    /// We're in an `Esc [` sequence, collecting up to three numeric
+    /// parameters. It's a mouse report if it started `Esc [ <`.
+    Csi {
+        params: [u16; 3],
+        separators: usize,
+        mouse: bool,
+    },
+    /// We've had `Esc O`
+    Ss3,
+}
+
+/// Decodes bytes into events, and queues them up
+struct Decoder {
+    state: ParseState,
+    queue: RingBuffer<Event, QUEUE_LEN>,
+    /// Have we asked the terminal to report the mouse?
+    mouse_reporting: bool,
+    /// Where we last put the mouse pointer, in pixels
+    pointer: (i16, i16),
+    /// The mouse buttons being held down
+    buttons: u8,
+}
+
+/// Read whatever has arrived on the given port (if we have one), and
+/// whatever the script is typing, and decode it.
+///
+/// We leave bytes in the port if our queue is getting full. We can't poll a
+/// port whose reads block (like `semi0`), so we ignore one of those.
+pub fn poll(port: Option<&mut dyn SerialPort>) {
+    let mut port = port.filter(|port| !port.read_blocks());
+    if let Some(port) = port.as_mut() {
+        let mut decoder = DECODER.lock();
+        if !decoder.mouse_reporting {
+            SerialWriter(&mut **port)
+                .write_str(ENABLE_MOUSE_REPORTING)
+                .unwrap();
+            decoder.mouse_reporting = true;
+        }
+    }
+    // Don't hold the lock while the script runs, as it can push keys too
+    while DECODER.lock().queue.len() <= QUEUE_LEN - MAX_EVENTS_PER_BYTE {
+        let Some(byte) = next_byte(&mut port) else {
+            break;
+        };
+        DECODER.lock().feed(byte);
+    }
+    let mut decoder = DECODER.lock();
+    if let ParseState::Escape { since_us } = decoder.state {
+        // If there's no room for the Escape key, we try again next time
+        if timer::now_us().wrapping_sub(since_us) >= ESCAPE_TIMEOUT_US
+            && decoder.has_room(Decoder::tap_len(0))
+        {
+            decoder.state = ParseState::Ground;
+            decoder.tap(KeyCode::Escape, 0);
+        }
+    }
+}
+
+/// Get the next byte to decode: from the script if it's typing, otherwise
+/// from the port (if we have one).
+fn next_byte(port: &mut Option<&mut dyn SerialPort>) -> Option<u8> {
+    #[cfg(feature = "script")]
+    if let Some(byte) = script::keyboard_byte() {
+        return Some(byte);
+    }
+    port.as_mut()?.read()
+}
+
+/// Stop using the given port as the keyboard.
+///
+/// We ask its terminal to stop reporting the mouse, whether or not we asked
+/// it to start (it might have been a previous boot that did), and ask
+/// whichever port we use next to start.
+pub fn release(port: &mut dyn SerialPort) {
+    SerialWriter(port)
+        .write_str(DISABLE_MOUSE_REPORTING)
+        .unwrap();
+    DECODER.lock().mouse_reporting = false;
+}
+
+/// Get the oldest event we've decoded.
+pub fn next_event() -> Option<Event> {
+    DECODER.lock().queue.pop()
+}
+
+impl Decoder {
+    /// Handle one byte from the port
+    fn feed(&mut self, byte: u8) {
+        match self.state {
+            ParseState::Ground if byte == ESC => {
+                self.state = ParseState::Escape {
+                    since_us: timer::now_us(),
+                };
+            }
+            ParseState::Ground => self.character(byte, 0),
+            ParseState::Escape { .. } => match byte {
+                b'[' => {
+                    self.state = ParseState::Csi {
+                        params: [0; 3],
+                        separators: 0,
+                        mouse: false,
+                    };
+                }
+                b'O' => self.state = ParseState::Ss3,
+                ESC => {
+                    // The first one must have been the Escape key
+                    self.tap(KeyCode::Escape, 0);
+                    self.state = ParseState::Escape {
+                        since_us: timer::now_us(),
+                    };
+                }
+                _ => {
+                    self.state = ParseState::Ground;
+                    self.character(byte, ALT);
+                }
+            },
+            ParseState::Csi {
+                mut params,
+                mut separators,
+                mut mouse,
+            } => match byte {
+                b'0'..=b'9' => {
+                    if let Some(param) = params.get_mut(separators) {
+                        *param = param
+                            .saturating_mul(10)
+                            .saturating_add(u16::from(byte - b'0'));
+                    }
+                    self.state = ParseState::Csi {
+                        params,
+                        separators,
+                        mouse,
+                    };
+                }
+                b';' => {
+                    separators += 1;
+                    self.state = ParseState::Csi {
+                        params,
+                        separators,
+                        mouse,
+                    };
+                }
+                b'<' => {
+                    mouse = true;
+                    self.state = ParseState::Csi {
+                        params,
+                        separators,
+                        mouse,
+                    };
+                }
+                b'M' | b'm' if mouse => {
+                    self.state = ParseState::Ground;
+                    self.mouse(params, byte == b'm');
+                }
+                0x40..=0x7E => {
+                    self.state = ParseState::Ground;
+                    self.csi(byte, params, separators);
+                }
+                _ => {
+                    // Intermediate bytes, which we don't use
+                }
+            },
+            ParseState::Ss3 => {
+                self.state = ParseState::Ground;
+                if let Some(key) = Self::final_byte_key(byte) {
+                    self.tap(key, 0);
+                }
+            }
+        }
+    }
+
+    /// Handle the end of an `Esc [` sequence
+    fn csi(&mut self, final_byte: u8, params: [u16; 3], separators: usize) {
+        // The second parameter, if there is one, is the modifiers plus one
+        let modifiers = if separators >= 1 {
+            params[1].saturating_sub(1) as u8
+        } else {
+            0
+        };
+        let key = match final_byte {
+            b'~' => match params[0] {
+                1 | 7 => KeyCode::Home,
+                2 => KeyCode::Insert,
+                3 => KeyCode::Delete,
+                4 | 8 => KeyCode::End,
+                5 => KeyCode::PageUp,
+                6 => KeyCode::PageDown,
+                11 => KeyCode::F1,
+                12 => KeyCode::F2,
+                13 => KeyCode::F3,
+                14 => KeyCode::F4,
+                15 => KeyCode::F5,
+                17 => KeyCode::F6,
+                18 => KeyCode::F7,
+                19 => KeyCode::F8,
+                20 => KeyCode::F9,
+                21 => KeyCode::F10,
+                23 => KeyCode::F11,
+                24 => KeyCode::F12,
+                _ => return,
+            },
+            // Shift-Tab
+            b'Z' => {
+                self.tap(KeyCode::Tab, SHIFT);
+                return;
+            }
+            _ => match Self::final_byte_key(final_byte) {
+                Some(key) => key,
+                None => return,
+            },
+        };
+        self.tap(key, modifiers);
+    }
+
+    /// Handle a mouse report, which is `Esc [ < b ; column ; row` and then
+    /// `M` for a press or a movement, or `m` for a release.
+    fn mouse(&mut self, params: [u16; 3], released: bool) {
+        let [code, column, row] = params;
+        if (code & 64) != 0 {
+            // The scroll wheel, which we can't report
+            return;
+        }
+        if (code & 32) == 0 {
+            // A button was pressed or released, rather than the mouse moving
+            let button = match code & 0x03 {
+                0 => MOUSE_LEFT,
+                1 => MOUSE_MIDDLE,
+                2 => MOUSE_RIGHT,
+                _ => 0,
+            };
+            if released {
+                self.buttons &= !button;
+            } else {
+                self.buttons |= button;
+            }
+        }
+        let (x, y) = cell_centre(column, row);
+        let event = Event::Mouse {
+            x: x - self.pointer.0,
+            y: y - self.pointer.1,
+            buttons: self.buttons,
+        };
+        // If the queue is full, the next report moves the pointer all the way
+        if self.queue.push(event).is_ok() {
+            self.pointer = (x, y);
+        }
+    }
+
+    /// The keys we get from `Esc [ <x>` and `Esc O <x>`
+    fn final_byte_key(final_byte: u8) -> Option<KeyCode> {
+        let key = match final_byte {
+            b'A' => KeyCode::ArrowUp,
+            b'B' => KeyCode::ArrowDown,
+            b'C' => KeyCode::ArrowRight,
+            b'D' => KeyCode::ArrowLeft,
+            b'H' => KeyCode::Home,
+            b'F' => KeyCode::End,
+            b'P' => KeyCode::F1,
+            b'Q' => KeyCode::F2,
+            b'R' => KeyCode::F3,
+            b'S' => KeyCode::F4,
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    /// Handle a character that isn't part of an escape sequence
+    fn character(&mut self, byte: u8, modifiers: u8) {
+        let (key, extra_modifiers) = match byte {
+            b'\r' | b'\n' => (KeyCode::Return, 0),
+            b'\t' => (KeyCode::Tab, 0),
+            0x08 | 0x7F => (KeyCode::Backspace, 0),
+            0x00 => (KeyCode::Spacebar, CTRL),
+            0x01..=0x1A => (LETTERS[usize::from(byte - 0x01)], CTRL),
+            0x1C => (KeyCode::Oem7, CTRL),
+            0x1D => (KeyCode::Oem6, CTRL),
+            0x1E => (KeyCode::Key6, CTRL | SHIFT),
+            0x1F => (KeyCode::OemMinus, CTRL | SHIFT),
+            0x20..=0x7E => printable_key(byte),
+            // Esc is handled elsewhere, and we don't do UTF-8
+            _ => return,
+        };
+        self.tap(key, modifiers | extra_modifiers);
+    }
+
+    /// Press and release a key, holding down the given modifiers.
+    ///
+    /// If there isn't room in the queue for all of that, we drop the lot, so
+    /// a key is never left held down.
+    fn tap(&mut self, key: KeyCode, modifiers: u8) {
+        if !self.has_room(Self::tap_len(modifiers)) {
+            return;
+        }
+        for (bit, modifier) in MODIFIER_KEYS {
+            if (modifiers & bit) != 0 {
+                let _ = self.queue.push(Event::KeyPress(modifier));
+            }
+        }
+        let _ = self.queue.push(Event::KeyPress(key));
+        let _ = self.queue.push(Event::KeyRelease(key));
+        for (bit, modifier) in MODIFIER_KEYS.into_iter().rev() {
+            if (modifiers & bit) != 0 {
+                let _ = self.queue.push(Event::KeyRelease(modifier));
+            }
+        }
+    }
+
+    /// How many events `tap` queues up for a key with the given modifiers
+    fn tap_len(modifiers: u8) -> usize {
+        let held = MODIFIER_KEYS
+            .iter()
+            .filter(|(bit, _)| (modifiers & bit) != 0)
+            .count();
+        2 + (2 * held)
+    }
+
+    /// Is there room in the queue for this many more events?
+    fn has_room(&self, events: usize) -> bool {
+        QUEUE_LEN - self.queue.len() >= events
+    }
+}
+
+/// Work out where the middle of a cell is on our screen, in pixels.
+///
+/// `column` and `row` count from one, as the terminal counts them. We use the
+/// text grid of the current mode, or a grid of 8x16 cells in a graphics mode.
+fn cell_centre(column: u16, row: u16) -> (i16, i16) {
+    let mode = video::mode();
+    let cell_height = match mode.format() {
+        common::video::Format::Text8x8 => 8,
+        _ => 16,
+    };
+    let columns = mode.horizontal_pixels() / 8;
+    let rows = mode.vertical_lines() / cell_height;
+    let column = column.clamp(1, columns) - 1;
+    let row = row.clamp(1, rows) - 1;
+    (
+        ((column * 8) + 4) as i16,
+        ((row * cell_height) + (cell_height / 2)) as i16,
+    )
+}
+
+/// Work out which key (and whether Shift) makes a printable ASCII character
+/// on a US-English keyboard.
+fn printable_key(byte: u8) -> (KeyCode, u8) {
+    match byte {
+        b'a'..=b'z' => (LETTERS[usize::from(byte - b'a')], 0),
+        b'A'..=b'Z' => (LETTERS[usize::from(byte - b'A')], SHIFT),
+        b'0'..=b'9' => (DIGITS[usize::from(byte - b'0')], 0),
+        b')' => (KeyCode::Key0, SHIFT),
+        b'!' => (KeyCode::Key1, SHIFT),
+        b'@' => (KeyCode::Key2, SHIFT),
+        b'#' => (KeyCode::Key3, SHIFT),
+        b'$' => (KeyCode::Key4, SHIFT),
+        b'%' => (KeyCode::Key5, SHIFT),
+        b'^' => (KeyCode::Key6, SHIFT),
+        b'&' => (KeyCode::Key7, SHIFT),
+        b'*' => (KeyCode::Key8, SHIFT),
+        b'(' => (KeyCode::Key9, SHIFT),
+        b'-' => (KeyCode::OemMinus, 0),
+        b'_' => (KeyCode::OemMinus, SHIFT),
+        b'=' => (KeyCode::OemPlus, 0),
+        b'+' => (KeyCode::OemPlus, SHIFT),
+        b'[' => (KeyCode::Oem4, 0),
+        b'{' => (KeyCode::Oem4, SHIFT),
+        b']' => (KeyCode::Oem6, 0),
+        b'}' => (KeyCode::Oem6, SHIFT),
+        b'\\' => (KeyCode::Oem7, 0),
+        b'|' => (KeyCode::Oem7, SHIFT),
+        b';' => (KeyCode::Oem1, 0),
+        b':' => (KeyCode::Oem1, SHIFT),
+        b'\'' => (KeyCode::Oem3, 0),
+        b'"' => (KeyCode::Oem3, SHIFT),
+        b'`' => (KeyCode::Oem8, 0),
+        b'~' => (KeyCode::Oem8, SHIFT),
+        b',' => (KeyCode::OemComma, 0),
+        b'<' => (KeyCode::OemComma, SHIFT),
+        b'.' => (KeyCode::OemPeriod, 0),
+        b'>' => (KeyCode::OemPeriod, SHIFT),
+        b'/' => (KeyCode::Oem2, 0),
+        b'?' => (KeyCode::Oem2, SHIFT),
+        _ => (KeyCode::Spacebar, 0),
+    }
+}
+
+/// The letter keys, from A to Z
+const LETTERS: [KeyCode; 26] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+];
+
+/// The number keys along the top, from 0 to 9
+const DIGITS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
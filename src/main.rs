@@ -5,11 +5,26 @@
 #![no_std]
 #![no_main]
 
+mod config;
+mod font;
+mod fpgaio;
+mod input;
+mod mirror;
 mod mutex;
+mod ring;
+mod rtc;
+mod screenshot;
+#[cfg(feature = "script")]
+mod script;
+mod semihosting;
+mod setup;
+mod timer;
+mod uart;
+mod video;
 
 use core::fmt::Write;
 
-use cortex_m_rt::entry;
+use cortex_m_rt::{entry, exception};
 use neotron_common_bios as common;
 
 extern "C" {
@@ -21,9 +36,6 @@ extern "C" {
     static mut _disk_end: u32;
 }
 
-/// Where the OS can put the text characters
-static mut VRAM: [(u8, u8); 80 * 50] = [(0, 0); 80 * 50];
-
 /// The clock speed of the peripheral subsystem on an SSE-300 SoC an on MPS3 board
 const PERIPHERAL_CLOCK: u32 = 25_000_000;
 
@@ -84,81 +96,301 @@ static API_CALLS: common::Api = common::Api {
     compare_and_swap_bool,
 };
 
-/// A driver for CMSDK Uart
-struct Uart<const ADDR: usize>();
-
-impl<const ADDR: usize> Uart<ADDR> {
-    const STATUS_TX_FULL: u32 = 1 << 0;
-    const STATUS_RX_NON_EMPTY: u32 = 1 << 1;
+/// The interrupts we use on the MPS3-AN547.
+///
+/// The numbers come from the AN547 Application Note.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u16)]
+enum Interrupt {
+    Uart0Rx = 33,
+    Uart0Tx = 34,
+    Uart1Rx = 35,
+    Uart1Tx = 36,
+    Uart2Rx = 37,
+    Uart2Tx = 38,
+    Uart3Rx = 39,
+    Uart3Tx = 40,
+    Uart4Rx = 41,
+    Uart4Tx = 42,
+    Uart5Rx = 125,
+    Uart5Tx = 126,
+}
 
-    /// Turn on TX and RX
-    fn enable(&mut self, baudrate: u32, system_clock: u32) {
-        let divider = system_clock / baudrate;
-        self.set_bauddiv(divider);
-        self.set_control(0b0000_0011);
+impl Interrupt {
+    /// Every interrupt we have a handler for
+    const ALL: [Interrupt; 12] = [
+        Interrupt::Uart0Rx,
+        Interrupt::Uart0Tx,
+        Interrupt::Uart1Rx,
+        Interrupt::Uart1Tx,
+        Interrupt::Uart2Rx,
+        Interrupt::Uart2Tx,
+        Interrupt::Uart3Rx,
+        Interrupt::Uart3Tx,
+        Interrupt::Uart4Rx,
+        Interrupt::Uart4Tx,
+        Interrupt::Uart5Rx,
+        Interrupt::Uart5Tx,
+    ];
+
+    /// Find the interrupt with the given IRQ number
+    fn from_irqn(irqn: i16) -> Option<Interrupt> {
+        Self::ALL.iter().copied().find(|i| *i as i16 == irqn)
     }
+}
 
-    /// Write a byte (blocking if there's no space)
-    fn write(&mut self, byte: u8) {
-        // Check the Buffer Full bit
-        while (self.get_status() & Self::STATUS_TX_FULL) != 0 {}
-        self.set_data(byte as u32);
+unsafe impl cortex_m::interrupt::InterruptNumber for Interrupt {
+    fn number(self) -> u16 {
+        self as u16
     }
+}
+
+/// The CMSDK UARTs on the MPS3-AN547, at their Secure addresses
+type Uart0 = uart::Uart<0x5930_3000>;
+type Uart1 = uart::Uart<0x5930_4000>;
+type Uart2 = uart::Uart<0x5930_5000>;
+type Uart3 = uart::Uart<0x5930_6000>;
+type Uart4 = uart::Uart<0x5930_7000>;
+type Uart5 = uart::Uart<0x5930_8000>;
+
+/// The FPGA I/O block on the MPS3-AN547, at its Secure address
+type FpgaIo = fpgaio::FpgaIo<0x5930_2000>;
+
+/// The PL031 Real Time Clock on the MPS3-AN547, at its Secure address
+type Rtc = rtc::Clock<0x5930_B000>;
+
+/// The names of our serial devices, in device number order
+static SERIAL_NAMES: [&str; 7] = ["ser0", "ser1", "ser2", "ser3", "ser4", "ser5", "semi0"];
+
+/// The names of our block devices, in device number order
+static BLOCK_DEV_NAMES: [&str; 1] = ["ddr0"];
+
+/// The names of our I2C buses, in bus number order
+static I2C_BUS_NAMES: [&str; 1] = ["fpgaio"];
+
+/// Something we can send bytes to and receive bytes from, like a UART
+trait SerialPort {
+    /// Check the given settings are supported, and if so, turn on TX and RX
+    /// with those settings.
+    fn configure(&mut self, config: &common::serial::Config) -> Result<(), common::Error>;
+
+    /// Try and write a byte, returning `false` if there's no space
+    fn try_write(&mut self, byte: u8) -> bool;
 
     /// Try and read a byte
-    fn read(&mut self) -> Option<u8> {
-        if (self.get_status() & Self::STATUS_RX_NON_EMPTY) != 0 {
-            Some(self.get_data() as u8)
-        } else {
-            None
+    fn read(&mut self) -> Option<u8>;
+
+    /// Find out if any received data has been lost since the last time this
+    /// was called.
+    fn take_error(&mut self) -> Option<common::Error> {
+        None
+    }
+
+    /// Does `read` wait for a byte to arrive?
+    ///
+    /// If so, `serial_read` only reads this port when it's given a timeout,
+    /// and then only one byte at a time.
+    fn read_blocks(&self) -> bool {
+        false
+    }
+}
+
+/// How a serial port translates line endings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+enum NewlineMode {
+    /// Pass every byte through untouched. Use this for binary transfers.
+    Raw = 0,
+    /// Send every `\n` as `\r\n`, so a terminal moves the cursor to the
+    /// start of the next line.
+    LfToCrLf = 1,
+    /// As `LfToCrLf`, and also turn every `\r` received into `\r\n`, for
+    /// terminals where the Enter key only sends `\r`.
+    CrToCrLf = 2,
+}
+
+impl NewlineMode {
+    /// Convert the number we store in the configuration back into a mode
+    fn from_u8(value: u8) -> Option<NewlineMode> {
+        match value {
+            0 => Some(NewlineMode::Raw),
+            1 => Some(NewlineMode::LfToCrLf),
+            2 => Some(NewlineMode::CrToCrLf),
+            _ => None,
+        }
+    }
+
+    /// The mode after this one, for cycling through them in the setup menu
+    fn next(self) -> NewlineMode {
+        match self {
+            NewlineMode::Raw => NewlineMode::LfToCrLf,
+            NewlineMode::LfToCrLf => NewlineMode::CrToCrLf,
+            NewlineMode::CrToCrLf => NewlineMode::Raw,
         }
     }
 
-    /// Read the data register
-    fn get_data(&mut self) -> u32 {
-        let ptr = ADDR as *mut u32;
-        unsafe { ptr.read_volatile() }
+    /// A short description, for the setup menu
+    fn name(self) -> &'static str {
+        match self {
+            NewlineMode::Raw => "raw",
+            NewlineMode::LfToCrLf => "send \\n as \\r\\n",
+            NewlineMode::CrToCrLf => "send \\n as \\r\\n, receive \\r as \\r\\n",
+        }
+    }
+
+    /// Do we send `\n` as `\r\n`?
+    fn expands_lf(self) -> bool {
+        self != NewlineMode::Raw
+    }
+
+    /// Do we receive `\r` as `\r\n`?
+    fn expands_cr(self) -> bool {
+        self == NewlineMode::CrToCrLf
+    }
+}
+
+/// The newline translation each serial port starts with.
+///
+/// The consoles get `\r\n` like they always have; the spare UARTs are left
+/// alone so they can carry binary data.
+const DEFAULT_NEWLINE_MODES: [NewlineMode; 7] = [
+    NewlineMode::LfToCrLf,
+    NewlineMode::Raw,
+    NewlineMode::Raw,
+    NewlineMode::Raw,
+    NewlineMode::Raw,
+    NewlineMode::Raw,
+    NewlineMode::LfToCrLf,
+];
+
+/// Describes the hardware in the system
+struct Hardware {
+    _cp: cortex_m::Peripherals,
+    uart0: Uart0,
+    uart1: Uart1,
+    uart2: Uart2,
+    uart3: Uart3,
+    uart4: Uart4,
+    uart5: Uart5,
+    semi0: semihosting::Console,
+    rtc: Rtc,
+    fpgaio: FpgaIo,
+    /// The settings each serial port is currently using
+    serial_configs: [Option<common::serial::Config>; 7],
+    /// The BIOS settings from the configuration store
+    settings: config::BiosSettings,
+    /// Which serial ports owe the OS a `\n`, because we received a `\r` and
+    /// are expanding it to `\r\n`.
+    pending_lf: [bool; 7],
+}
+
+impl Hardware {
+    /// Get the serial port with the given device number
+    fn serial_port(&mut self, device: u8) -> Option<&mut dyn SerialPort> {
+        match device {
+            0 => Some(&mut self.uart0),
+            1 => Some(&mut self.uart1),
+            2 => Some(&mut self.uart2),
+            3 => Some(&mut self.uart3),
+            4 => Some(&mut self.uart4),
+            5 => Some(&mut self.uart5),
+            6 => Some(&mut self.semi0),
+            _ => None,
+        }
     }
 
-    /// Write the data register
-    fn set_data(&mut self, data: u32) {
-        let ptr = ADDR as *mut u32;
-        unsafe { ptr.write_volatile(data) }
+    /// Apply new settings to a serial port, and remember them if they were
+    /// accepted.
+    fn serial_configure(
+        &mut self,
+        device: u8,
+        config: common::serial::Config,
+    ) -> Result<(), common::Error> {
+        let port = self
+            .serial_port(device)
+            .ok_or(common::Error::InvalidDevice)?;
+        port.configure(&config)?;
+        self.serial_configs[device as usize] = Some(config);
+        Ok(())
     }
 
-    /// Read the status register
-    fn get_status(&self) -> u32 {
-        let ptr = (ADDR + 4) as *mut u32;
-        unsafe { ptr.read_volatile() }
+    /// Get the settings a serial port is currently using
+    fn serial_config(&self, device: u8) -> Option<&common::serial::Config> {
+        self.serial_configs.get(device as usize)?.as_ref()
+    }
+
+    /// Get the serial port the BIOS talks to you on.
+    ///
+    /// If the settings name a port we don't have, you get `ser0`.
+    fn console_port(&mut self) -> &mut dyn SerialPort {
+        let device = if usize::from(self.settings.console_port) < SERIAL_NAMES.len() {
+            self.settings.console_port
+        } else {
+            0
+        };
+        self.serial_port(device).unwrap()
+    }
+
+    /// Get something we can `write!` to the BIOS console with.
+    fn console(&mut self) -> SerialWriter<'_> {
+        SerialWriter(self.console_port())
+    }
+
+    /// Update the text-mode screen mirror, if we have one.
+    fn poll_mirror(&mut self) {
+        let Some(device) = self.settings.mirror_port else {
+            return;
+        };
+        // The screen would get mixed up with what's on the port already
+        if device == self.settings.console_port || Some(device) == self.settings.keyboard_port {
+            return;
+        }
+        if let Some(port) = self.serial_port(device) {
+            mirror::poll(port);
+        }
     }
 
-    /// Set the control register
-    fn set_control(&mut self, data: u32) {
-        let ptr = (ADDR + 8) as *mut u32;
-        unsafe { ptr.write_volatile(data) }
+    /// Get something we can `write!` debug messages to, if we have a debug
+    /// port.
+    fn debug(&mut self) -> Option<SerialWriter<'_>> {
+        let device = self.settings.debug_port?;
+        self.serial_port(device).map(SerialWriter)
     }
 
-    /// Set the baud rate divider register
-    fn set_bauddiv(&mut self, data: u32) {
-        let ptr = (ADDR + 16) as *mut u32;
-        unsafe { ptr.write_volatile(data) }
+    /// Look for changes on the buttons and switches, and decode anything
+    /// that has arrived on the keyboard port (if we have one) or that the
+    /// script is typing.
+    fn poll_input(&mut self) {
+        self.fpgaio.poll();
+        // The OS reads the console itself, so we mustn't take bytes from it
+        let port = match self.settings.keyboard_port {
+            Some(device) if device != self.settings.console_port => self.serial_port(device),
+            _ => None,
+        };
+        input::poll(port);
     }
 }
 
-impl<const N: usize> core::fmt::Write for Uart<N> {
+/// Lets us `write!` to any serial port, waiting for space as required.
+struct SerialWriter<'a>(&'a mut dyn SerialPort);
+
+impl core::fmt::Write for SerialWriter<'_> {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for b in s.bytes() {
-            self.write(b);
+        for byte in s.bytes() {
+            while !self.0.try_write(byte) {}
         }
         Ok(())
     }
 }
 
-/// Describes the hardware in the system
-struct Hardware {
-    _cp: cortex_m::Peripherals,
-    uart0: Uart<0x5930_3000>,
-}
+/// The settings every serial port starts with
+const DEFAULT_SERIAL_CONFIG: common::serial::Config = common::serial::Config {
+    data_rate_bps: 115_200,
+    data_bits: common::serial::DataBits::Eight,
+    stop_bits: common::serial::StopBits::One,
+    parity: common::serial::Parity::None,
+    handshaking: common::serial::Handshaking::None,
+};
 
 static HARDWARE: mutex::NeoMutex<Option<Hardware>> = mutex::NeoMutex::new(None);
 
@@ -171,10 +403,71 @@ fn bios_main() -> ! {
     // Set up the hardware
     let mut h = hardware_setup();
 
+    // Apply the BIOS settings
+    let (settings, config_status) = config::init();
+    h.settings = settings;
+    let console_baud = h.settings.console_baud;
+    let console_baud_ok = h
+        .serial_configure(
+            h.settings.console_port,
+            common::serial::Config {
+                data_rate_bps: console_baud,
+                ..DEFAULT_SERIAL_CONFIG
+            },
+        )
+        .is_ok();
+
     // Print the BIOS version
-    write!(h.uart0, "Neotron QEMU BIOS {}\r\n", BIOS_VERSION).unwrap();
-    write!(h.uart0, "Disk Start: {:p}\r\n", unsafe { &_disk_start }).unwrap();
-    write!(h.uart0, "Disk End  : {:p}\r\n", unsafe { &_disk_end }).unwrap();
+    let mut console = h.console();
+    write!(console, "Neotron QEMU BIOS {}\r\n", BIOS_VERSION).unwrap();
+    write!(console, "Disk Start: {:p}\r\n", unsafe { &_disk_start }).unwrap();
+    write!(console, "Disk End  : {:p}\r\n", unsafe { &_disk_end }).unwrap();
+    write!(console, "Config    : {}\r\n", config_status).unwrap();
+    if !console_baud_ok {
+        write!(
+            console,
+            "Console   : can't run at {} baud\r\n",
+            console_baud
+        )
+        .unwrap();
+    }
+
+    // Ask the host what the time is, as the RTC might not know
+    let host_secs = match h.settings.clock_source {
+        config::ClockSource::Host => semihosting::host_time()
+            .and_then(|unix_secs| unix_secs.checked_sub(rtc::NEOTRON_EPOCH_AS_UNIX)),
+        config::ClockSource::Rtc => None,
+    };
+    let time_source = match host_secs {
+        Some(secs) => {
+            h.rtc.set_time(common::Time { secs, nsecs: 0 });
+            "host"
+        }
+        None => "RTC",
+    };
+    let now = rtc::Timestamp(h.rtc.get_time());
+    write!(
+        h.console(),
+        "Time      : {} (from {})\r\n",
+        now,
+        time_source
+    )
+    .unwrap();
+
+    // A script is for running unattended, so don't wait for setup
+    #[cfg(feature = "script")]
+    let offer_setup = match script::load() {
+        script::LoadStatus::Empty => true,
+        script_status => {
+            write!(h.console(), "Script    : {}\r\n", script_status).unwrap();
+            false
+        }
+    };
+    #[cfg(not(feature = "script"))]
+    let offer_setup = true;
+    if offer_setup {
+        setup::offer(&mut h);
+    }
 
     *HARDWARE.lock() = Some(h);
 
@@ -183,12 +476,36 @@ fn bios_main() -> ! {
 
 /// Configure the hardware
 fn hardware_setup() -> Hardware {
-    let mut uart0 = Uart();
-    uart0.enable(115200, PERIPHERAL_CLOCK);
-    Hardware {
-        _cp: cortex_m::Peripherals::take().expect("Couldn't get hardware"),
-        uart0,
+    let mut cp = cortex_m::Peripherals::take().expect("Couldn't get hardware");
+    timer::init(&mut cp.SYST);
+    let mut hw = Hardware {
+        _cp: cp,
+        uart0: Uart0::new(&uart::UART_STATES[0]),
+        uart1: Uart1::new(&uart::UART_STATES[1]),
+        uart2: Uart2::new(&uart::UART_STATES[2]),
+        uart3: Uart3::new(&uart::UART_STATES[3]),
+        uart4: Uart4::new(&uart::UART_STATES[4]),
+        uart5: Uart5::new(&uart::UART_STATES[5]),
+        semi0: semihosting::Console,
+        rtc: Rtc::start(),
+        fpgaio: FpgaIo::new(),
+        serial_configs: Default::default(),
+        settings: config::BiosSettings::default(),
+        pending_lf: [false; 7],
+    };
+    for device in 0..SERIAL_NAMES.len() as u8 {
+        hw.serial_configure(device, DEFAULT_SERIAL_CONFIG)
+            .expect("Default serial config rejected");
     }
+    // On Armv6-M our vector table is too short for these, so SysTick polls
+    // the UARTs instead
+    #[cfg(not(armv6m))]
+    for interrupt in Interrupt::ALL {
+        unsafe {
+            cortex_m::peripheral::NVIC::unmask(interrupt);
+        }
+    }
+    hw
 }
 
 /// Returns the version number of the BIOS API.
@@ -221,13 +538,14 @@ pub extern "C" fn bios_version_get() -> common::FfiString<'static> {
 /// reflect the raw hardware, in a similar manner to the registers exposed
 /// by a memory-mapped UART peripheral.
 pub extern "C" fn serial_get_info(device: u8) -> common::FfiOption<common::serial::DeviceInfo> {
-    if device == 0 {
-        common::FfiOption::Some(common::serial::DeviceInfo {
-            name: common::FfiString::new("ser0"),
+    match SERIAL_NAMES.get(device as usize) {
+        Some(name) => common::FfiOption::Some(common::serial::DeviceInfo {
+            name: common::FfiString::new(name),
+            // There's no device type for a debugger console, so semi0 says
+            // it's a UART too.
             device_type: common::serial::DeviceType::TtlUart,
-        })
-    } else {
-        common::FfiOption::None
+        }),
+        None => common::FfiOption::None,
     }
 }
 
@@ -237,13 +555,9 @@ pub extern "C" fn serial_configure(
     device: u8,
     config: common::serial::Config,
 ) -> common::ApiResult<()> {
-    if device == 0 {
-        let mut hw = HARDWARE.lock();
-        let hw = hw.as_mut().unwrap();
-        // Ignore all the settings and just turn the thing on
-        hw.uart0.enable(config.data_rate_bps, PERIPHERAL_CLOCK);
-    }
-    common::ApiResult::Ok(())
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    hw.serial_configure(device, config).into()
 }
 
 /// Write bytes to a serial port. There is no sense of 'opening' or
@@ -251,25 +565,44 @@ pub extern "C" fn serial_configure(
 /// value is `Ok(n)`, the value `n` may be less than the size of the given
 /// buffer. If so, that means not all of the data could be transmitted -
 /// only the first `n` bytes were.
+///
+/// If there is no timeout, we wait for as long as it takes to queue all the
+/// bytes. Otherwise we give up when the timeout expires.
+///
+/// Line endings are translated according to the port's `NewlineMode`.
 pub extern "C" fn serial_write(
     device: u8,
     data: common::FfiByteSlice,
-    _timeout: common::FfiOption<common::Timeout>,
+    timeout: common::FfiOption<common::Timeout>,
 ) -> common::ApiResult<usize> {
-    if device == 0 {
-        let mut hw = HARDWARE.lock();
-        let hw = hw.as_mut().unwrap();
-        let bytes = data.as_slice();
-        for b in bytes {
-            if *b == b'\n' {
-                hw.uart0.write(b'\r');
-            }
-            hw.uart0.write(*b);
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    let Some(&newline_mode) = hw.settings.newline_modes.get(device as usize) else {
+        return common::ApiResult::Err(common::Error::InvalidDevice);
+    };
+    let Some(port) = hw.serial_port(device) else {
+        return common::ApiResult::Err(common::Error::InvalidDevice);
+    };
+    let deadline = Option::from(timeout).map(timer::Deadline::new);
+    let mut write_byte = |byte| loop {
+        if port.try_write(byte) {
+            break true;
         }
-        common::ApiResult::Ok(bytes.len())
-    } else {
-        common::ApiResult::Err(common::Error::InvalidDevice)
+        if deadline.as_ref().is_some_and(timer::Deadline::has_passed) {
+            break false;
+        }
+    };
+    let mut count = 0;
+    for b in data.as_slice() {
+        if *b == b'\n' && newline_mode.expands_lf() && !write_byte(b'\r') {
+            break;
+        }
+        if !write_byte(*b) {
+            break;
+        }
+        count += 1;
     }
+    common::ApiResult::Ok(count)
 }
 
 /// Read bytes from a serial port. There is no sense of 'opening' or
@@ -277,29 +610,104 @@ pub extern "C" fn serial_write(
 ///  is `Ok(n)`, the value `n` may be less than the size of the given buffer.
 ///  If so, that means not all of the data could be received - only the
 ///  first `n` bytes were filled in.
+///
+/// If there is no timeout, we return immediately with whatever bytes have
+/// already arrived. Otherwise we wait until at least one byte arrives, or the
+/// timeout expires.
+///
+/// Reads from `semi0` can only block, as the host gives us no way to check
+/// for a byte. So with no timeout they never return anything, and with a
+/// timeout they wait for one byte, however long that takes.
+///
+/// Line endings are translated according to the port's `NewlineMode`.
+///
+/// If any received bytes were lost since the last call, we return
+/// `DeviceError` (see `uart::ERROR_RX_OVERRUN` and
+/// `uart::ERROR_RX_BUFFER_FULL`) once, without reading anything. The next
+/// call reads the data as usual.
+///
+/// On the BIOS console, `Ctrl-\` takes a screenshot (see the `screenshot`
+/// module) and isn't passed on. If that fails, we say so on the console.
+///
+/// With the `script` feature, anything the script (see the `script` module)
+/// sends to the port arrives here as if it came from the port.
 pub extern "C" fn serial_read(
     device: u8,
     mut data: common::FfiBuffer,
-    _timeout: common::FfiOption<common::Timeout>,
+    timeout: common::FfiOption<common::Timeout>,
 ) -> common::ApiResult<usize> {
-    if device == 0 {
-        let mut hw = HARDWARE.lock();
-        let hw = hw.as_mut().unwrap();
-        let Some(bytes) = data.as_mut_slice() else {
-            return common::ApiResult::Err(common::Error::UnsupportedConfiguration(0));
-        };
-        let mut count = 0;
-        for b in bytes {
-            if let Some(read) = hw.uart0.read() {
-                *b = read;
-                count += 1;
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    let Some(&newline_mode) = hw.settings.newline_modes.get(device as usize) else {
+        return common::ApiResult::Err(common::Error::InvalidDevice);
+    };
+    let mut pending_lf = hw.pending_lf[device as usize];
+    let is_console = device == hw.settings.console_port;
+    let Some(port) = hw.serial_port(device) else {
+        return common::ApiResult::Err(common::Error::InvalidDevice);
+    };
+    let Some(bytes) = data.as_mut_slice() else {
+        return common::ApiResult::Err(common::Error::UnsupportedConfiguration(0));
+    };
+    if let Some(error) = port.take_error() {
+        return common::ApiResult::Err(error);
+    }
+    let deadline = Option::from(timeout).map(timer::Deadline::new);
+    // A port whose reads block can't return immediately, so we only read it
+    // if we were given a timeout, and then only one byte
+    let port_readable = !port.read_blocks() || deadline.is_some();
+    let max_count = if port.read_blocks() {
+        bytes.len().min(1)
+    } else {
+        bytes.len()
+    };
+    let mut count = 0;
+    let mut screenshot_failed = false;
+    loop {
+        while count < max_count {
+            if pending_lf {
+                pending_lf = false;
+                bytes[count] = b'\n';
+            } else if let Some(read) = next_serial_byte(device, &mut *port, port_readable) {
+                if is_console && read == screenshot::HOTKEY {
+                    // This one is for us, not the OS
+                    screenshot_failed |= !screenshot::take();
+                    continue;
+                }
+                pending_lf = read == b'\r' && newline_mode.expands_cr();
+                bytes[count] = read;
             } else {
                 break;
             }
+            count += 1;
+        }
+        match &deadline {
+            Some(deadline) if count == 0 && !bytes.is_empty() && !deadline.has_passed() => {
+                // Sleep until the next interrupt (at most a millisecond)
+                cortex_m::asm::wfi();
+            }
+            _ => break,
         }
-        common::ApiResult::Ok(count)
+    }
+    hw.pending_lf[device as usize] = pending_lf;
+    if screenshot_failed {
+        write!(hw.console(), "\r\nCouldn't save screenshot\r\n").unwrap();
+    }
+    common::ApiResult::Ok(count)
+}
+
+/// Get the next byte for `serial_read`: from the script if it's sending
+/// one, otherwise from the port (if we're allowed to read it).
+#[cfg_attr(not(feature = "script"), allow(unused_variables))]
+fn next_serial_byte(device: u8, port: &mut dyn SerialPort, port_readable: bool) -> Option<u8> {
+    #[cfg(feature = "script")]
+    if let Some(byte) = script::serial_byte(device) {
+        return Some(byte);
+    }
+    if port_readable {
+        port.read()
     } else {
-        common::ApiResult::Err(common::Error::InvalidDevice)
+        None
     }
 }
 
@@ -315,8 +723,12 @@ pub extern "C" fn serial_read(
 ///
 /// If the BIOS does not have a battery-backed clock, or if that battery has
 /// failed to keep time, the system starts up assuming it is the epoch.
+///
+/// We read the PL031 RTC, which QEMU starts at the host's time.
 pub extern "C" fn time_clock_get() -> common::Time {
-    common::Time { secs: 0, nsecs: 0 }
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    hw.rtc.get_time()
 }
 
 /// Set the current wall time.
@@ -328,7 +740,11 @@ pub extern "C" fn time_clock_get() -> common::Time {
 /// time (e.g. the user has updated the current time, or if you get a GPS
 /// fix). The BIOS should push the time out to the battery-backed Real
 /// Time Clock, if it has one.
-pub extern "C" fn time_clock_set(_time: common::Time) {}
+pub extern "C" fn time_clock_set(time: common::Time) {
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    hw.rtc.set_time(time);
+}
 
 /// Get the configuration data block.
 ///
@@ -336,16 +752,33 @@ pub extern "C" fn time_clock_set(_time: common::Time) {}
 /// How it stores them is up to the BIOS - it could be EEPROM, or battery-backed
 /// SRAM.
 ///
-/// However, in this BIOS we are linked to the OS so we can cheat and encode the
-/// bytes with postcard directly.
+/// We return whatever was last given to `configuration_set`. If nothing valid
+/// has been saved, we are linked to the OS so we can cheat and encode a
+/// default configuration with postcard directly.
 pub extern "C" fn configuration_get(mut buffer: common::FfiBuffer) -> common::ApiResult<usize> {
-    let mut config = neotron_os::OsConfig::default();
-    config.set_serial_console_on(115_200);
-    config.set_vga_console(None);
-
     let Some(buffer) = buffer.as_mut_slice() else {
         return common::ApiResult::Err(common::Error::UnsupportedConfiguration(0));
     };
+
+    if let Some(saved) = config::os_config() {
+        let Some(dest) = buffer.get_mut(..saved.len()) else {
+            return common::ApiResult::Err(common::Error::UnsupportedConfiguration(0));
+        };
+        dest.copy_from_slice(saved);
+        return common::ApiResult::Ok(saved.len());
+    }
+
+    let console_baud = {
+        let hw = HARDWARE.lock();
+        let hw = hw.as_ref().unwrap();
+        hw.serial_config(0)
+            .map(|c| c.data_rate_bps)
+            .unwrap_or(DEFAULT_SERIAL_CONFIG.data_rate_bps)
+    };
+    let mut config = neotron_os::OsConfig::default();
+    config.set_serial_console_on(console_baud);
+    config.set_vga_console(None);
+
     match postcard::to_slice(&config, buffer) {
         Ok(slice) => common::ApiResult::Ok(slice.len()),
         Err(_e) => common::ApiResult::Err(common::Error::UnsupportedConfiguration(0)),
@@ -354,14 +787,20 @@ pub extern "C" fn configuration_get(mut buffer: common::FfiBuffer) -> common::Ap
 
 /// Set the configuration data block.
 ///
-/// See `configuration_get`.
-pub extern "C" fn configuration_set(_buffer: common::FfiByteSlice) -> common::ApiResult<()> {
-    common::ApiResult::Ok(())
+/// See `configuration_get`. The block is kept in DDR4 SDRAM, so it survives
+/// a reset but not restarting QEMU. Our own settings are saved alongside it.
+pub extern "C" fn configuration_set(buffer: common::FfiByteSlice) -> common::ApiResult<()> {
+    let hw = HARDWARE.lock();
+    let hw = hw.as_ref().unwrap();
+    config::save_os_config(buffer.as_slice(), &hw.settings).into()
 }
 
 /// Does this Neotron BIOS support this video mode?
-pub extern "C" fn video_is_valid_mode(_mode: common::video::Mode) -> bool {
-    false
+///
+/// We support the text modes that fit in our VRAM (which is up to 80x60), and
+/// the graphics modes with up to 8 bits per pixel.
+pub extern "C" fn video_is_valid_mode(mode: common::video::Mode) -> bool {
+    video::is_valid_mode(mode)
 }
 
 /// Switch to a new video mode.
@@ -373,8 +812,8 @@ pub extern "C" fn video_is_valid_mode(_mode: common::video::Mode) -> bool {
 /// `video_get_framebuffer` will return `null`. You must then supply a
 /// pointer to a block of size `Mode::frame_size_bytes()` to
 /// `video_set_framebuffer` before any video will appear.
-pub extern "C" fn video_set_mode(_mode: common::video::Mode) -> common::ApiResult<()> {
-    common::ApiResult::Err(common::Error::UnsupportedConfiguration(0))
+pub extern "C" fn video_set_mode(mode: common::video::Mode) -> common::ApiResult<()> {
+    video::set_mode(mode).into()
 }
 
 /// Returns the video mode the BIOS is currently in.
@@ -383,7 +822,7 @@ pub extern "C" fn video_set_mode(_mode: common::video::Mode) -> common::ApiResul
 /// the value - this is the `default` video mode which can always be
 /// serviced without supplying extra RAM.
 pub extern "C" fn video_get_mode() -> common::video::Mode {
-    unsafe { common::video::Mode::from_u8(0) }
+    video::mode()
 }
 
 /// Get the framebuffer address.
@@ -400,7 +839,7 @@ pub extern "C" fn video_get_mode() -> common::video::Mode {
 /// to provide the 'basic' text buffer experience from reserves, so this
 /// function will never return `null` on start-up.
 pub extern "C" fn video_get_framebuffer() -> *mut u8 {
-    unsafe { VRAM.as_mut_ptr() as *mut u8 }
+    video::framebuffer()
 }
 
 /// Set the framebuffer address.
@@ -414,15 +853,16 @@ pub extern "C" fn video_get_framebuffer() -> *mut u8 {
 ///
 /// The pointer must point to enough video memory to handle the current video
 /// mode, and any future video mode you set.
-pub unsafe extern "C" fn video_set_framebuffer(_buffer: *const u8) -> common::ApiResult<()> {
-    common::ApiResult::Err(common::Error::Unimplemented)
+pub unsafe extern "C" fn video_set_framebuffer(buffer: *const u8) -> common::ApiResult<()> {
+    unsafe { video::set_framebuffer(buffer as *mut u8) }.into()
 }
 
 /// Find out whether the given video mode needs more VRAM than we currently have.
 ///
-/// The answer is no for any currently supported video mode (which is just the four text modes right now).
-pub extern "C" fn video_mode_needs_vram(_mode: common::video::Mode) -> bool {
-    false
+/// We have enough for any of the text modes we support, and any graphics mode
+/// up to 640x480 at 8 bits per pixel.
+pub extern "C" fn video_mode_needs_vram(mode: common::video::Mode) -> bool {
+    video::mode_needs_vram(mode)
 }
 
 /// Find out how large a given region of memory is.
@@ -482,13 +922,50 @@ pub extern "C" fn memory_get_region(region: u8) -> common::FfiOption<common::Mem
 /// a single KeyCode enum. Plus, Scan Code Set 2 is a pain, because most of the
 /// 'extended' keys they added on the IBM PC/AT actually generate two bytes, not
 /// one. It's much nicer when your Scan Codes always have one byte per key.
+///
+/// Under QEMU we have no keyboard, so we decode key presses from whatever
+/// arrives on the serial port picked in the setup menu (see the `input`
+/// module). The MPS3 push-buttons and switches also appear as keys (see the
+/// `fpgaio` module).
 pub extern "C" fn hid_get_event() -> common::ApiResult<common::FfiOption<common::hid::HidEvent>> {
-    common::ApiResult::Ok(common::FfiOption::None)
+    // The OS calls this a lot, so it's a good time to update the mirror
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    hw.poll_mirror();
+    hw.poll_input();
+    match input::next_event() {
+        Some(event) => common::ApiResult::Ok(common::FfiOption::Some(event.into())),
+        None => common::ApiResult::Ok(common::FfiOption::None),
+    }
 }
 
 /// Control the keyboard LEDs.
-pub extern "C" fn hid_set_leds(_leds: common::hid::KeyboardLeds) -> common::ApiResult<()> {
-    common::ApiResult::Err(common::Error::Unimplemented)
+///
+/// We show them on the MPS3 user LEDs, and log any change on the debug port
+/// (if there is one).
+pub extern "C" fn hid_set_leds(leds: common::hid::KeyboardLeds) -> common::ApiResult<()> {
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    let mut value = 0;
+    if leds.num_lock() {
+        value |= fpgaio::LED_NUM_LOCK;
+    }
+    if leds.caps_lock() {
+        value |= fpgaio::LED_CAPS_LOCK;
+    }
+    if leds.scroll_lock() {
+        value |= fpgaio::LED_SCROLL_LOCK;
+    }
+    let mask = fpgaio::LED_NUM_LOCK | fpgaio::LED_CAPS_LOCK | fpgaio::LED_SCROLL_LOCK;
+    let old_leds = hw.fpgaio.leds();
+    hw.fpgaio.set_leds(mask, value);
+    let new_leds = hw.fpgaio.leds();
+    if new_leds != old_leds {
+        if let Some(mut debug) = hw.debug() {
+            write!(debug, "LEDs: {:010b}\r\n", new_leds).unwrap();
+        }
+    }
+    common::ApiResult::Ok(())
 }
 
 /// Wait for the next occurence of the specified video scan-line.
@@ -517,35 +994,93 @@ pub extern "C" fn hid_set_leds(_leds: common::hid::KeyboardLeds) -> common::ApiR
 /// You can also use this for a crude `16.7 ms` delay but note that
 /// some video modes run at `70 Hz` and so this would then give you a
 /// `14.3ms` second delay.
-pub extern "C" fn video_wait_for_line(_line: u16) {}
+///
+/// We have no real video signal, so we work out where an imaginary one would
+/// be from the tick counter.
+pub extern "C" fn video_wait_for_line(line: u16) {
+    {
+        let mut hw = HARDWARE.lock();
+        hw.as_mut().unwrap().poll_mirror();
+    }
+    video::wait_for_line(line);
+}
 
 /// Read the RGB palette.
-extern "C" fn video_get_palette(_index: u8) -> common::FfiOption<common::video::RGBColour> {
-    common::FfiOption::None
+///
+/// We have 256 entries, so every index is valid.
+extern "C" fn video_get_palette(index: u8) -> common::FfiOption<common::video::RGBColour> {
+    let [red, green, blue] = video::palette_entry(index);
+    common::FfiOption::Some(common::video::RGBColour::from_rgb(red, green, blue))
 }
 
 /// Update the RGB palette.
-extern "C" fn video_set_palette(_index: u8, _rgb: common::video::RGBColour) {}
+extern "C" fn video_set_palette(index: u8, rgb: common::video::RGBColour) {
+    video::set_palette_entry(index, [rgb.red(), rgb.green(), rgb.blue()]);
+}
 
 /// Update all the RGB palette
+///
+/// Entries beyond the 256 we have are ignored.
+///
+/// # Safety
+///
+/// `palette` must point to `length` colours, or be null.
 unsafe extern "C" fn video_set_whole_palette(
-    _palette: *const common::video::RGBColour,
-    _length: usize,
+    palette: *const common::video::RGBColour,
+    length: usize,
 ) {
+    if palette.is_null() {
+        return;
+    }
+    let palette = unsafe { core::slice::from_raw_parts(palette, length.min(256)) };
+    for (index, rgb) in palette.iter().enumerate() {
+        video::set_palette_entry(index as u8, [rgb.red(), rgb.green(), rgb.blue()]);
+    }
 }
 
-extern "C" fn i2c_bus_get_info(_i2c_bus: u8) -> common::FfiOption<common::i2c::BusInfo> {
-    common::FfiOption::None
+/// Get information about the I2C buses in the system.
+///
+/// We have no real ones, but the MPS3 push-buttons and switches look like a
+/// device on bus 0 (see the `fpgaio` module).
+extern "C" fn i2c_bus_get_info(i2c_bus: u8) -> common::FfiOption<common::i2c::BusInfo> {
+    match I2C_BUS_NAMES.get(usize::from(i2c_bus)) {
+        Some(name) => common::FfiOption::Some(common::i2c::BusInfo {
+            name: common::FfiString::new(name),
+        }),
+        None => common::FfiOption::None,
+    }
 }
 
+/// Write `tx` then `tx2` to a device on an I2C bus, then fill `rx` from it.
+///
+/// The only device is the push-buttons and switches, which want the number
+/// of the first register to read, and nothing else.
 extern "C" fn i2c_write_read(
-    _i2c_bus: u8,
-    _i2c_device_address: u8,
-    _tx: common::FfiByteSlice,
-    _tx2: common::FfiByteSlice,
-    _rx: common::FfiBuffer,
+    i2c_bus: u8,
+    i2c_device_address: u8,
+    tx: common::FfiByteSlice,
+    tx2: common::FfiByteSlice,
+    mut rx: common::FfiBuffer,
 ) -> common::ApiResult<()> {
-    common::ApiResult::Err(common::Error::Unimplemented)
+    if usize::from(i2c_bus) >= I2C_BUS_NAMES.len() {
+        return common::ApiResult::Err(common::Error::InvalidDevice);
+    }
+    if i2c_device_address != fpgaio::I2C_ADDRESS {
+        return common::ApiResult::Err(common::Error::DeviceError(0));
+    }
+    let mut written = tx.as_slice().iter().chain(tx2.as_slice());
+    let register = written.next().copied().unwrap_or(0);
+    if written.next().is_some() {
+        // Our registers are read-only
+        return common::ApiResult::Err(common::Error::UnsupportedConfiguration);
+    }
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    hw.fpgaio.poll();
+    if let Some(buffer) = rx.as_mut_slice() {
+        hw.fpgaio.read_registers(usize::from(register), buffer);
+    }
+    common::ApiResult::Ok(())
 }
 
 extern "C" fn audio_mixer_channel_get_info(
@@ -646,16 +1181,20 @@ pub extern "C" fn block_dev_get_info(
     device: u8,
 ) -> common::FfiOption<common::block_dev::DeviceInfo> {
     if device == 0 {
+        let read_only = {
+            let hw = HARDWARE.lock();
+            hw.as_ref().unwrap().settings.disk_write_protect
+        };
         // Our emulated disk drive, sitting in DDR4 SDRAM
         common::FfiOption::Some(common::block_dev::DeviceInfo {
-            name: common::FfiString::new("ddr0"),
+            name: common::FfiString::new(BLOCK_DEV_NAMES[0]),
             device_type: common::block_dev::DeviceType::HardDiskDrive,
             block_size: 512,
             num_blocks: unsafe { DISK_IMAGE.len() } as u64 / 512,
             ejectable: false,
             removable: false,
             media_present: true,
-            read_only: false,
+            read_only,
         })
     } else {
         common::FfiOption::None
@@ -679,6 +1218,13 @@ pub extern "C" fn block_write(
     if device != 0 {
         return common::ApiResult::Err(common::Error::InvalidDevice);
     }
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    if hw.settings.disk_write_protect {
+        // Write-protected in the BIOS settings
+        return common::ApiResult::Err(common::Error::DeviceError(0));
+    }
+    hw.fpgaio.set_leds(fpgaio::LED_DISK, fpgaio::LED_DISK);
     let mut offset = (block.0 * 512) as usize;
     for b in data.as_slice() {
         unsafe {
@@ -686,6 +1232,7 @@ pub extern "C" fn block_write(
         }
         offset += 1;
     }
+    hw.fpgaio.set_leds(fpgaio::LED_DISK, 0);
     common::ApiResult::Ok(())
 }
 
@@ -706,6 +1253,9 @@ pub extern "C" fn block_read(
     if device != 0 {
         return common::ApiResult::Err(common::Error::InvalidDevice);
     }
+    let mut hw = HARDWARE.lock();
+    let hw = hw.as_mut().unwrap();
+    hw.fpgaio.set_leds(fpgaio::LED_DISK, fpgaio::LED_DISK);
     let mut offset = (block.0 * 512) as usize;
     for b in data.as_mut_slice().unwrap() {
         unsafe {
@@ -713,6 +1263,7 @@ pub extern "C" fn block_read(
         }
         offset += 1;
     }
+    hw.fpgaio.set_leds(fpgaio::LED_DISK, 0);
     common::ApiResult::Ok(())
 }
 
@@ -739,7 +1290,10 @@ extern "C" fn block_dev_eject(_dev_id: u8) -> common::ApiResult<()> {
 }
 
 /// Sleep the CPU until the next interrupt.
-extern "C" fn power_idle() {}
+extern "C" fn power_idle() {
+    let mut hw = HARDWARE.lock();
+    hw.as_mut().unwrap().poll_mirror();
+}
 
 extern "C" fn power_control(_mode: common::PowerMode) -> ! {
     loop {
@@ -764,19 +1318,98 @@ extern "C" fn compare_and_swap_bool(
     })
 }
 
+/// Get the number of ticks since boot.
+///
+/// This counter never wraps (well, not for half a million years).
 extern "C" fn time_ticks_get() -> common::Ticks {
-    common::Ticks(0)
+    common::Ticks(timer::now_us())
 }
 
 /// We have a 1 MHz timer
 extern "C" fn time_ticks_per_second() -> common::Ticks {
-    common::Ticks(1_000_000)
+    common::Ticks(timer::TICKS_PER_SECOND)
+}
+
+/// Called for any interrupt that doesn't have its own handler.
+///
+/// We don't have a Peripheral Access Crate for the AN547, so all the device
+/// interrupts come through here and we dispatch them ourselves.
+#[exception]
+fn DefaultHandler(irqn: i16) {
+    match Interrupt::from_irqn(irqn) {
+        Some(Interrupt::Uart0Rx | Interrupt::Uart0Tx) => {
+            Uart0::new(&uart::UART_STATES[0]).interrupt();
+        }
+        Some(Interrupt::Uart1Rx | Interrupt::Uart1Tx) => {
+            Uart1::new(&uart::UART_STATES[1]).interrupt();
+        }
+        Some(Interrupt::Uart2Rx | Interrupt::Uart2Tx) => {
+            Uart2::new(&uart::UART_STATES[2]).interrupt();
+        }
+        Some(Interrupt::Uart3Rx | Interrupt::Uart3Tx) => {
+            Uart3::new(&uart::UART_STATES[3]).interrupt();
+        }
+        Some(Interrupt::Uart4Rx | Interrupt::Uart4Tx) => {
+            Uart4::new(&uart::UART_STATES[4]).interrupt();
+        }
+        Some(Interrupt::Uart5Rx | Interrupt::Uart5Tx) => {
+            Uart5::new(&uart::UART_STATES[5]).interrupt();
+        }
+        None => {
+            // Spurious interrupt
+        }
+    }
+}
+
+/// Called when something goes badly wrong, like a semihosting call that
+/// nothing answers.
+///
+/// We can't use `#[exception]` here, because cortex-m-rt insists a HardFault
+/// handler never returns, and we return from the semihosting faults (see
+/// `semihosting::recover`). cortex-m-rt's trampoline passes us the stacked
+/// registers, and branches here with the exception return address in LR, so
+/// returning from this function returns from the exception. Any other fault
+/// stops here.
+#[no_mangle]
+unsafe extern "C" fn HardFault(frame: *mut u32) {
+    if semihosting::recover(frame) {
+        return;
+    }
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+/// Called once a millisecond
+#[exception]
+fn SysTick() {
+    timer::tick();
+    Rtc::watch();
+    #[cfg(armv6m)]
+    poll_uarts();
+}
+
+/// Do the UARTs' interrupt work, as we can't take their interrupts.
+///
+/// cortex-m-rt only gives Armv6-M a 32 entry interrupt table, and the UART
+/// interrupts are all above that, so on Armv6-M we call this once a
+/// millisecond instead.
+#[cfg(armv6m)]
+fn poll_uarts() {
+    Uart0::new(&uart::UART_STATES[0]).interrupt();
+    Uart1::new(&uart::UART_STATES[1]).interrupt();
+    Uart2::new(&uart::UART_STATES[2]).interrupt();
+    Uart3::new(&uart::UART_STATES[3]).interrupt();
+    Uart4::new(&uart::UART_STATES[4]).interrupt();
+    Uart5::new(&uart::UART_STATES[5]).interrupt();
 }
 
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    let mut uart0: Uart<0x5930_3000> = Uart();
+    let mut uart0 = Uart0::new(&uart::UART_STATES[0]);
     let _ = write!(uart0, "PANIC!\r\n{:#?}\r\n", info);
+    // Interrupts might be off, so push everything out by hand
+    uart0.flush();
     loop {
         cortex_m::asm::wfi();
     }
@@ -0,0 +1,195 @@
+//! Mirrors the text-mode VRAM to a serial port, as an ANSI/VT100 terminal.
+//!
+//! QEMU has no display for the MPS3-AN547, so this is how you see the OS's
+//! VGA console. We keep a copy of what we think the terminal is showing, and
+//! every so often we compare it with VRAM and send whatever has changed,
+//! using cursor positioning and SGR colour sequences. The colours come from
+//! the palette, as 24-bit colour, so your terminal needs to support that.
+//!
+//! We are polled from the BIOS calls the OS makes while it's waiting for
+//! something (like `hid_get_event` and `video_wait_for_line`), not from an
+//! interrupt, because the serial ports belong to the OS thread. We never wait
+//! for the port: what we want to send goes into a small buffer, and whatever
+//! won't fit in the port's TX buffer waits until we're next polled.
+
+use core::fmt::Write;
+
+use crate::{mutex::NeoMutex, timer, video, SerialPort};
+
+/// How often we look for changes
+const POLL_INTERVAL_US: u64 = 20_000;
+
+/// How many bytes we can have waiting to go to the port
+const PENDING_LEN: usize = 256;
+
+/// The most bytes it takes to draw one cell (moving the cursor, changing
+/// the colours and sending the character)
+const MAX_CELL_LEN: usize = 64;
+
+/// Light grey on black, which is what we clear the terminal to
+const CLEAR_ATTR: u8 = 0x07;
+
+/// What we think the terminal is showing.
+///
+/// This is too big for the BIOS RAM, so it goes in the FPGA SRAM. It is
+/// `NOLOAD`, so it's garbage until we first clear the terminal.
+#[link_section = ".fpga_sram"]
+static mut SHADOW: [(u8, u8); video::MAX_TEXT_CELLS] = [(0, 0); video::MAX_TEXT_CELLS];
+
+/// Our state
+static STATE: NeoMutex<State> = NeoMutex::new(State {
+    drawn_mode: None,
+    drawn_palette: 0,
+    last_poll_us: 0,
+    cursor: None,
+    attr: None,
+    pending: Pending {
+        bytes: [0; PENDING_LEN],
+        start: 0,
+        end: 0,
+    },
+});
+
+/// What we know about the terminal, besides what's on it
+struct State {
+    /// The video mode the terminal is showing. `None` means we need to
+    /// clear the terminal and start again.
+    drawn_mode: Option<u8>,
+    /// The palette generation the terminal is showing
+    drawn_palette: u32,
+    /// When we last looked for changes
+    last_poll_us: u64,
+    /// The cell the terminal's cursor is on, if we know
+    cursor: Option<usize>,
+    /// The attribute the terminal is drawing with, if we know
+    attr: Option<u8>,
+    /// What we've decided to send, but haven't sent yet
+    pending: Pending,
+}
+
+/// Bytes waiting to go to the port
+struct Pending {
+    /// The bytes
+    bytes: [u8; PENDING_LEN],
+    /// The next byte to send
+    start: usize,
+    /// One past the last byte to send
+    end: usize,
+}
+
+impl Pending {
+    /// How many more bytes we can take
+    fn room(&self) -> usize {
+        PENDING_LEN - self.end
+    }
+
+    /// Send as much as the port will take without waiting.
+    ///
+    /// Returns `true` if we sent everything.
+    fn send(&mut self, port: &mut dyn SerialPort) -> bool {
+        while self.start < self.end {
+            if !port.try_write(self.bytes[self.start]) {
+                return false;
+            }
+            self.start += 1;
+        }
+        self.start = 0;
+        self.end = 0;
+        true
+    }
+}
+
+impl Write for Pending {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let space = self
+            .bytes
+            .get_mut(self.end..self.end + s.len())
+            .ok_or(core::fmt::Error)?;
+        space.copy_from_slice(s.as_bytes());
+        self.end += s.len();
+        Ok(())
+    }
+}
+
+/// Send anything that has changed in VRAM to the given port.
+///
+/// Does nothing if it hasn't been long enough since the last time, or if
+/// we're not in a text mode. If the port still hasn't taken everything we
+/// sent last time, we only carry on sending that.
+pub fn poll(port: &mut dyn SerialPort) {
+    let mut state = STATE.lock();
+    if !state.pending.send(port) {
+        return;
+    }
+    let now_us = timer::now_us();
+    if now_us.wrapping_sub(state.last_poll_us) < POLL_INTERVAL_US {
+        return;
+    }
+    state.last_poll_us = now_us;
+
+    let mode = video::mode();
+    let (Some(width), Some(height)) = (mode.text_width(), mode.text_height()) else {
+        return;
+    };
+    let width = usize::from(width);
+    let cells = (width * usize::from(height)).min(video::MAX_TEXT_CELLS);
+    let shadow = unsafe { &mut *core::ptr::addr_of_mut!(SHADOW) };
+    let state = &mut *state;
+    let out = &mut state.pending;
+
+    let palette = video::palette_generation();
+    if state.drawn_mode != Some(mode.as_u8()) || state.drawn_palette != palette {
+        // Hide the cursor, and clear the screen to a colour we know
+        write_sgr(out, CLEAR_ATTR);
+        write!(out, "\x1b[?25l\x1b[2J").unwrap();
+        shadow[..cells].fill((b' ', CLEAR_ATTR));
+        state.drawn_mode = Some(mode.as_u8());
+        state.drawn_palette = palette;
+        state.cursor = None;
+        state.attr = Some(CLEAR_ATTR);
+    }
+
+    for (index, drawn) in shadow[..cells].iter_mut().enumerate() {
+        let Some(cell) = video::text_cell(index) else {
+            break;
+        };
+        if *drawn == cell {
+            continue;
+        }
+        if out.room() < MAX_CELL_LEN {
+            break;
+        }
+        if state.cursor != Some(index) {
+            write!(out, "\x1b[{};{}H", (index / width) + 1, (index % width) + 1).unwrap();
+        }
+        if state.attr != Some(cell.1) {
+            write_sgr(out, cell.1);
+            state.attr = Some(cell.1);
+        }
+        out.write_char(video::glyph_to_char(cell.0)).unwrap();
+        *drawn = cell;
+        // Terminals disagree about what happens at the end of a line
+        state.cursor = if (index + 1) % width == 0 {
+            None
+        } else {
+            Some(index + 1)
+        };
+    }
+    out.send(port);
+}
+
+/// Send the SGR sequence which selects the colours in a VGA attribute byte.
+///
+/// The bottom four bits are the foreground colour, the next three are the
+/// background colour and the top bit is blink.
+fn write_sgr(out: &mut Pending, attr: u8) {
+    let [fg_red, fg_green, fg_blue] = video::text_colour(attr & 0x0F);
+    let [bg_red, bg_green, bg_blue] = video::text_colour((attr >> 4) & 0x07);
+    let blink = if (attr & 0x80) != 0 { ";5" } else { "" };
+    write!(
+        out,
+        "\x1b[0;38;2;{};{};{};48;2;{};{};{}{}m",
+        fg_red, fg_green, fg_blue, bg_red, bg_green, bg_blue, blink
+    )
+    .unwrap();
+}
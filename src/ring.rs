@@ -0,0 +1,77 @@
+//! A lock-free ring buffer.
+//!
+//! Armv6-M has no compare-and-swap, but a single-producer, single-consumer
+//! queue only needs atomic loads and stores. That lets one side live in an
+//! interrupt handler and the other in thread mode without a lock.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A single-producer, single-consumer queue holding up to `N` items.
+///
+/// The head and tail are free-running counters, so `N` should be a power of
+/// two to keep the wrap-around cheap.
+pub struct RingBuffer<T, const N: usize> {
+    buffer: core::cell::UnsafeCell<[T; N]>,
+    /// Counts items written. Only the producer modifies this.
+    head: AtomicUsize,
+    /// Counts items read. Only the consumer modifies this.
+    tail: AtomicUsize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    /// Create a new, empty, ring buffer.
+    ///
+    /// The `init` value is only used to fill the unused slots.
+    pub const fn new(init: T) -> RingBuffer<T, N> {
+        RingBuffer {
+            buffer: core::cell::UnsafeCell::new([init; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add an item to the queue.
+    ///
+    /// Gives the item back if the queue is full. Must only be called by the
+    /// producer.
+    pub fn push(&self, item: T) -> Result<(), T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) >= N {
+            return Err(item);
+        }
+        unsafe {
+            (*self.buffer.get())[head % N] = item;
+        }
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Take the oldest item from the queue.
+    ///
+    /// Must only be called by the consumer.
+    pub fn pop(&self) -> Option<T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let item = unsafe { (*self.buffer.get())[tail % N] };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+
+    /// How many items are waiting in the queue.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        head.wrapping_sub(tail)
+    }
+
+    /// Is the queue empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+unsafe impl<T: Send, const N: usize> Sync for RingBuffer<T, N> {}
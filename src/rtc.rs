@@ -0,0 +1,247 @@
+//! A driver for the Arm PL031 Real Time Clock.
+//!
+//! The PL031 just counts seconds. QEMU starts it at the host's idea of the
+//! current time, as seconds since the Unix epoch, so that's what we assume
+//! it holds.
+
+use neotron_common_bios as common;
+
+use core::cell::Cell;
+
+/// The Neotron epoch (2000-01-01T00:00:00Z), in seconds since the Unix
+/// epoch (1970-01-01T00:00:00Z).
+pub const NEOTRON_EPOCH_AS_UNIX: u32 = 946_684_800;
+
+/// Displays a Neotron time as an ISO 8601 date and time, in UTC.
+pub struct Timestamp(pub common::Time);
+
+impl core::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let unix_secs = u64::from(self.0.secs) + u64::from(NEOTRON_EPOCH_AS_UNIX);
+        let (year, month, day) = civil_from_days((unix_secs / 86_400) as u32);
+        let secs_of_day = unix_secs % 86_400;
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            (secs_of_day / 60) % 60,
+            secs_of_day % 60
+        )
+    }
+}
+
+/// Convert days since the Unix epoch into a (year, month, day) date.
+///
+/// This is Howard Hinnant's `civil_from_days` algorithm, simplified because
+/// we never go before 1970.
+fn civil_from_days(days: u32) -> (u32, u32, u32) {
+    // Count from 0000-03-01, so the leap day is at the end of the year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months also start from March
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u32::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a date and time, in UTC, into a Neotron time.
+///
+/// Returns `None` if the date doesn't exist, or is outside of what a
+/// Neotron time can hold.
+pub fn time_from_civil(
+    year: u32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+) -> Option<common::Time> {
+    if year > 9999 || !(1..=12).contains(&month) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day)?;
+    // Catches the 31st of February, and so on
+    if civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    let unix_secs = u64::from(days) * 86_400
+        + u64::from(hour) * 3600
+        + u64::from(minute) * 60
+        + u64::from(second);
+    let secs = unix_secs.checked_sub(u64::from(NEOTRON_EPOCH_AS_UNIX))?;
+    Some(common::Time {
+        secs: u32::try_from(secs).ok()?,
+        nsecs: 0,
+    })
+}
+
+/// Convert a (year, month, day) date into days since the Unix epoch.
+///
+/// This is the other half of Howard Hinnant's algorithm. Returns `None`
+/// for dates before 1970.
+fn days_from_civil(year: u32, month: u32, day: u32) -> Option<u32> {
+    // Count from 0000-03-01, as in `civil_from_days`
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * mp + 2) / 5 + day.checked_sub(1)?;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era.checked_mul(146_097)? + day_of_era).checked_sub(719_468)
+}
+
+/// What SysTick has seen of the RTC ticking over (see `Clock::watch`)
+static EDGE: critical_section::Mutex<Cell<Edge>> =
+    critical_section::Mutex::new(Cell::new(Edge::Idle));
+
+/// Where we are in finding the start of a second on the RTC
+#[derive(Debug, Copy, Clone)]
+enum Edge {
+    /// We aren't looking
+    Idle,
+    /// We're waiting for the RTC to move on from `secs`, and have been since
+    /// the tick count was `since_us`
+    Watching { secs: u32, since_us: u64 },
+    /// The RTC ticked over to `secs` when the tick count was `at_us`
+    Seen { secs: u32, at_us: u64 },
+}
+
+/// A wall clock, built from a PL031 and our tick counter.
+///
+/// The RTC only gives us whole seconds, so SysTick watches for it to tick
+/// over, and notes the tick count at that moment. After that we work the
+/// time out from the tick counter, so the fraction of a second is always
+/// right and the time never stands still. Until then (for up to a second
+/// after start-up), we don't know where in the second we are.
+pub struct Clock<const ADDR: usize> {
+    /// The time, in whole seconds since the Unix epoch, at our anchor
+    anchor_secs: u32,
+    /// How far into that second our anchor is
+    anchor_fraction_us: u64,
+    /// The tick count at our anchor
+    anchor_us: u64,
+}
+
+impl<const ADDR: usize> Clock<ADDR> {
+    /// The RTC is running
+    const CONTROL_START: u32 = 1 << 0;
+
+    /// How long we watch for the RTC to tick over after start-up
+    const START_TIMEOUT_US: u64 = 1_100_000;
+
+    /// Start the RTC (if it isn't already running), and start watching for
+    /// the start of the next second.
+    ///
+    /// If the RTC doesn't tick over, we carry on without knowing where in
+    /// the second we are.
+    pub fn start() -> Clock<ADDR> {
+        let mut clock = Clock {
+            anchor_secs: 0,
+            anchor_fraction_us: 0,
+            anchor_us: 0,
+        };
+        clock.set_control(Self::CONTROL_START);
+        clock.anchor_secs = clock.get_data();
+        clock.anchor_us = crate::timer::now_us();
+        critical_section::with(|cs| {
+            EDGE.borrow(cs).set(Edge::Watching {
+                secs: clock.anchor_secs,
+                since_us: clock.anchor_us,
+            });
+        });
+        clock
+    }
+
+    /// Look for the RTC ticking over. Call this from the SysTick exception
+    /// handler.
+    ///
+    /// This only reads the RTC until it has ticked over once (or we've given
+    /// up on it), so it's cheap.
+    pub fn watch() {
+        critical_section::with(|cs| {
+            let edge = EDGE.borrow(cs);
+            if let Edge::Watching { secs, since_us } = edge.get() {
+                let now_us = crate::timer::now_us();
+                let data = unsafe { (ADDR as *const u32).read_volatile() };
+                if data != secs {
+                    edge.set(Edge::Seen {
+                        secs: data,
+                        at_us: now_us,
+                    });
+                } else if now_us - since_us >= Self::START_TIMEOUT_US {
+                    edge.set(Edge::Idle);
+                }
+            }
+        });
+    }
+
+    /// Get the current time, relative to the Neotron epoch.
+    ///
+    /// If the RTC says it's before the Neotron epoch, you get the epoch.
+    pub fn get_time(&mut self) -> common::Time {
+        critical_section::with(|cs| {
+            let edge = EDGE.borrow(cs);
+            if let Edge::Seen { secs, at_us } = edge.get() {
+                self.anchor_secs = secs;
+                self.anchor_us = at_us;
+                self.anchor_fraction_us = 0;
+                edge.set(Edge::Idle);
+            }
+        });
+        let elapsed_us = crate::timer::now_us() - self.anchor_us + self.anchor_fraction_us;
+        let elapsed_secs = u32::try_from(elapsed_us / 1_000_000).unwrap_or(u32::MAX);
+        let secs = self.anchor_secs.saturating_add(elapsed_secs);
+        match secs.checked_sub(NEOTRON_EPOCH_AS_UNIX) {
+            Some(secs) => common::Time {
+                secs,
+                nsecs: (elapsed_us % 1_000_000) as u32 * 1000,
+            },
+            None => common::Time { secs: 0, nsecs: 0 },
+        }
+    }
+
+    /// Set the current time, relative to the Neotron epoch.
+    ///
+    /// The RTC only holds whole seconds, but we keep the fraction of a
+    /// second in our anchor.
+    pub fn set_time(&mut self, time: common::Time) {
+        let secs = time.secs.saturating_add(NEOTRON_EPOCH_AS_UNIX);
+        self.set_load(secs);
+        // Stop watching the RTC, as it would undo this
+        critical_section::with(|cs| EDGE.borrow(cs).set(Edge::Idle));
+        self.anchor_secs = secs;
+        self.anchor_us = crate::timer::now_us();
+        self.anchor_fraction_us = u64::from(time.nsecs.min(999_999_999) / 1000);
+    }
+
+    /// Read the data register (the current count)
+    fn get_data(&self) -> u32 {
+        let ptr = ADDR as *mut u32;
+        unsafe { ptr.read_volatile() }
+    }
+
+    /// Write the load register (which sets the current count)
+    fn set_load(&mut self, data: u32) {
+        let ptr = (ADDR + 8) as *mut u32;
+        unsafe { ptr.write_volatile(data) }
+    }
+
+    /// Set the control register
+    fn set_control(&mut self, data: u32) {
+        let ptr = (ADDR + 12) as *mut u32;
+        unsafe { ptr.write_volatile(data) }
+    }
+}
@@ -0,0 +1,141 @@
+//! Saves what's on the screen to files on the host.
+//!
+//! Press `Ctrl-\` on the BIOS console and we write `screenshot-NNN.ppm` (a
+//! picture, drawn with our font and the current palette) to wherever QEMU
+//! was started, using semihosting. In a text mode we also write
+//! `screenshot-NNN.txt` (just the characters). `NNN` counts up from `000`
+//! each time the BIOS starts.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use neotron_common_bios as common;
+
+use crate::{font, semihosting::HostFile, video};
+
+/// The key that takes a screenshot (`Ctrl-\`)
+pub const HOTKEY: u8 = 0x1C;
+
+/// The widest mode we might be in, in pixels
+const MAX_WIDTH: usize = 800;
+
+/// The widest text mode we might be in, at 8 pixels per glyph
+const MAX_COLUMNS: usize = MAX_WIDTH / 8;
+
+/// The number for the next screenshot
+static NEXT_NUMBER: AtomicU16 = AtomicU16::new(0);
+
+/// Save the screen to the next pair of files.
+///
+/// Returns `false` if there's nothing to save (because the OS hasn't given
+/// us a framebuffer), or the host wouldn't let us write the files.
+pub fn take() -> bool {
+    let mode = video::mode();
+    if video::framebuffer().is_null() || usize::from(mode.horizontal_pixels()) > MAX_WIDTH {
+        return false;
+    }
+    let number = NEXT_NUMBER.load(Ordering::Relaxed);
+    NEXT_NUMBER.store(number.wrapping_add(1), Ordering::Relaxed);
+
+    let (Some(columns), Some(rows)) = (mode.text_width(), mode.text_height()) else {
+        let (width, height) = (mode.horizontal_pixels(), mode.vertical_lines());
+        return HostFile::create(&file_name(number, b"ppm"))
+            .map(|mut file| save_pixels(&mut file, usize::from(width), usize::from(height)))
+            .unwrap_or(false);
+    };
+    let (columns, rows) = (usize::from(columns), usize::from(rows));
+    let glyph_height = match mode.format() {
+        common::video::Format::Text8x8 => 8,
+        _ => 16,
+    };
+    let picture_saved = HostFile::create(&file_name(number, b"ppm"))
+        .map(|mut file| save_picture(&mut file, columns, rows, glyph_height))
+        .unwrap_or(false);
+    let text_saved = HostFile::create(&file_name(number, b"txt"))
+        .map(|mut file| save_text(&mut file, columns, rows))
+        .unwrap_or(false);
+    picture_saved && text_saved
+}
+
+/// Make a null-terminated file name like `screenshot-001.ppm`.
+fn file_name(number: u16, extension: &[u8; 3]) -> [u8; 19] {
+    let mut name = *b"screenshot-000.xxx\0";
+    name[11] = b'0' + ((number / 100) % 10) as u8;
+    name[12] = b'0' + ((number / 10) % 10) as u8;
+    name[13] = b'0' + (number % 10) as u8;
+    name[15..18].copy_from_slice(extension);
+    name
+}
+
+/// Draw the text cells into a binary PPM file, one line of pixels at a time.
+fn save_picture(file: &mut HostFile, columns: usize, rows: usize, glyph_height: usize) -> bool {
+    let width = columns * 8;
+    if write!(file, "P6\n{} {}\n255\n", width, rows * glyph_height).is_err() {
+        return false;
+    }
+    let mut line = [0u8; MAX_WIDTH * 3];
+    for row in 0..rows {
+        for y in 0..glyph_height {
+            // Our font is 8 pixels high, so stretch it to fit
+            let font_row = y * 8 / glyph_height;
+            for column in 0..columns {
+                let (glyph, attr) = video::text_cell((row * columns) + column).unwrap_or((b' ', 0));
+                let bits = font::glyph_row(glyph, font_row);
+                let fg = video::text_colour(attr & 0x0F);
+                let bg = video::text_colour((attr >> 4) & 0x07);
+                for x in 0..8 {
+                    let colour = if (bits & (1 << x)) != 0 { fg } else { bg };
+                    let offset = ((column * 8) + x) * 3;
+                    line[offset..offset + 3].copy_from_slice(&colour);
+                }
+            }
+            if !file.write_all(&line[..width * 3]) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Copy the pixels of a graphics mode into a binary PPM file, one line at a
+/// time.
+fn save_pixels(file: &mut HostFile, width: usize, height: usize) -> bool {
+    if write!(file, "P6\n{} {}\n255\n", width, height).is_err() {
+        return false;
+    }
+    let mut line = [0u8; MAX_WIDTH * 3];
+    for y in 0..height {
+        for x in 0..width {
+            let colour = video::palette_entry(video::pixel(x, y).unwrap_or(0));
+            line[x * 3..(x * 3) + 3].copy_from_slice(&colour);
+        }
+        if !file.write_all(&line[..width * 3]) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Write the characters to a UTF-8 text file, one line per row, without
+/// any trailing spaces.
+fn save_text(file: &mut HostFile, columns: usize, rows: usize) -> bool {
+    // Room for every glyph as three bytes of UTF-8, plus a newline
+    let mut line = [0u8; (MAX_COLUMNS * 3) + 1];
+    for row in 0..rows {
+        let mut len = 0;
+        let mut trimmed_len = 0;
+        for column in 0..columns {
+            let (glyph, _attr) = video::text_cell((row * columns) + column).unwrap_or((b' ', 0));
+            let ch = video::glyph_to_char(glyph);
+            len += ch.encode_utf8(&mut line[len..]).len();
+            if ch != ' ' {
+                trimmed_len = len;
+            }
+        }
+        line[trimmed_len] = b'\n';
+        if !file.write_all(&line[..=trimmed_len]) {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,377 @@
+//! Plays back a script of input from the host, for automated tests.
+//!
+//! This is only built with the `script` feature. If there's a file called
+//! `neotron-script.txt` wherever QEMU was started, we load it (using
+//! semihosting) at boot, and play it back against the tick
+//! clock. It looks like this:
+//!
+//! ```text
+//! # Wait for the OS to boot, then list the disk
+//! at 500ms type "dir\n"
+//! at 2s send ser1 "hello\r"
+//! at 3s press 0
+//! at 3100ms release 0
+//! at 4s switch 2 on
+//! at 10s exit
+//! ```
+//!
+//! * `type` types the text on the keyboard, so it comes out of
+//!   `hid_get_event`.
+//! * `send` makes the bytes arrive on a serial port, so they come out of
+//!   `serial_read`.
+//! * `press` and `release` work a push-button, and `switch` turns a switch
+//!   on or off.
+//! * `exit` stops QEMU.
+//!
+//! Times are since the BIOS started, in `us`, `ms` or `s`. Text goes in
+//! single or double quotes, and can have the escapes `\n`, `\r`, `\t`, `\e`
+//! (Esc), `\xNN`, `\\`, `\'` and `\"`.
+//!
+//! The steps happen in order. A step doesn't start before its time, and
+//! doesn't start until the one before it has finished, so if the OS isn't
+//! reading the port some text is sent to, the script waits for it.
+
+use crate::{fpgaio, mutex::NeoMutex, semihosting::HostFile, timer, SERIAL_NAMES};
+
+/// The file on the host we load the script from
+const SCRIPT_PATH: &[u8] = b"neotron-script.txt\0";
+
+/// The biggest script we can load
+const MAX_SCRIPT_LEN: usize = 64 * 1024;
+
+/// The script we're playing.
+///
+/// This is too big for the BIOS RAM, so it goes in the FPGA SRAM. It is
+/// `NOLOAD`, so only the first `Player::len` bytes mean anything.
+#[link_section = ".fpga_sram"]
+static mut SCRIPT: [u8; MAX_SCRIPT_LEN] = [0; MAX_SCRIPT_LEN];
+
+/// Our state
+static PLAYER: NeoMutex<Player> = NeoMutex::new(Player {
+    len: 0,
+    next_line: 0,
+    step: None,
+});
+
+/// What we found when we looked for a script at start-up
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadStatus {
+    /// There's no script
+    Empty,
+    /// We loaded a script with this many steps
+    Loaded(usize),
+    /// The script is bigger than `MAX_SCRIPT_LEN`, so we won't play it
+    TooBig,
+    /// We didn't understand this line (counting from one), so we won't play
+    /// the script
+    BadLine(usize),
+}
+
+impl core::fmt::Display for LoadStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LoadStatus::Empty => write!(f, "none"),
+            LoadStatus::Loaded(steps) => write!(f, "{} steps", steps),
+            LoadStatus::TooBig => write!(f, "too big (max {} bytes)", MAX_SCRIPT_LEN),
+            LoadStatus::BadLine(line) => write!(f, "error on line {}", line),
+        }
+    }
+}
+
+/// Something a script can do
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Action {
+    /// Type the text on the keyboard
+    Type,
+    /// Send the text to the given serial port
+    Send(u8),
+    /// Press (`true`) or release (`false`) the given push-button
+    Button(usize, bool),
+    /// Turn the given switch on (`true`) or off (`false`)
+    Switch(usize, bool),
+    /// Stop QEMU
+    Exit,
+}
+
+/// One line of the script
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Step {
+    /// When the step starts, on the tick clock
+    at_us: u64,
+    /// What it does
+    action: Action,
+    /// Where the text we haven't used yet is, in the script. It still has
+    /// its escapes, but not its quotes.
+    text: (usize, usize),
+}
+
+/// Where we are in the script
+struct Player {
+    /// How long the script is
+    len: usize,
+    /// Where the next line starts
+    next_line: usize,
+    /// The step we're on, if we've started it
+    step: Option<Step>,
+}
+
+/// Look for a script on the host, and load it if it makes sense.
+///
+/// Call this once at start-up.
+pub fn load() -> LoadStatus {
+    let Some(mut file) = HostFile::open_read(SCRIPT_PATH) else {
+        return LoadStatus::Empty;
+    };
+    let script = unsafe { &mut *core::ptr::addr_of_mut!(SCRIPT) };
+    let len = file.read(script);
+    if len == script.len() {
+        return LoadStatus::TooBig;
+    }
+    let mut steps = 0;
+    for (index, (start, end)) in lines(&script[..len]).enumerate() {
+        match parse_line(script, start, end) {
+            Ok(Some(_)) => steps += 1,
+            Ok(None) => {}
+            Err(()) => return LoadStatus::BadLine(index + 1),
+        }
+    }
+    PLAYER.lock().len = len;
+    if steps == 0 {
+        LoadStatus::Empty
+    } else {
+        LoadStatus::Loaded(steps)
+    }
+}
+
+/// Get the next byte the script types on the keyboard, if it's time.
+pub fn keyboard_byte() -> Option<u8> {
+    PLAYER.lock().next_byte(Action::Type)
+}
+
+/// Get the next byte the script sends to the given serial port, if it's
+/// time.
+pub fn serial_byte(device: u8) -> Option<u8> {
+    PLAYER.lock().next_byte(Action::Send(device))
+}
+
+impl Player {
+    /// Run the steps that are due, until we get a byte for `wanted`, or we
+    /// run out of steps, or a step has to wait.
+    fn next_byte(&mut self, wanted: Action) -> Option<u8> {
+        while self.step_is_due() {
+            let step = self.step.as_mut()?;
+            match step.action {
+                Action::Button(button, down) => {
+                    fpgaio::script_button(button, down);
+                    self.step = None;
+                }
+                Action::Switch(switch, on) => {
+                    fpgaio::script_switch(switch, on);
+                    self.step = None;
+                }
+                Action::Exit => {
+                    cortex_m_semihosting::debug::exit(cortex_m_semihosting::debug::EXIT_SUCCESS);
+                    self.step = None;
+                }
+                action if action == wanted => {
+                    let (start, end) = step.text;
+                    let Some((byte, used)) = decode_byte(&script()[start..end]) else {
+                        // There wasn't any text
+                        self.step = None;
+                        continue;
+                    };
+                    step.text.0 += used;
+                    if step.text.0 == end {
+                        self.step = None;
+                    }
+                    return Some(byte);
+                }
+                _ => {
+                    // The step is for someone else
+                    return None;
+                }
+            }
+        }
+        None
+    }
+
+    /// Load the next step from the script if we need to, and say whether
+    /// it's time for it.
+    fn step_is_due(&mut self) -> bool {
+        while self.step.is_none() {
+            if self.next_line >= self.len {
+                return false;
+            }
+            let start = self.next_line;
+            let end = line_end(&script()[..self.len], start);
+            self.next_line = end + 1;
+            // We checked every line when we loaded the script
+            self.step = parse_line(script(), start, end).ok().flatten();
+        }
+        self.step.is_some_and(|step| timer::now_us() >= step.at_us)
+    }
+}
+
+/// Get the script we loaded.
+fn script() -> &'static [u8] {
+    unsafe { &*core::ptr::addr_of!(SCRIPT) }
+}
+
+/// Find where each line starts and ends (not counting the newline).
+fn lines(script: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut start = 0;
+    core::iter::from_fn(move || {
+        if start >= script.len() {
+            return None;
+        }
+        let end = line_end(script, start);
+        let line = (start, end);
+        start = end + 1;
+        Some(line)
+    })
+}
+
+/// Find where the line starting at `start` ends (not counting the newline).
+fn line_end(script: &[u8], start: usize) -> usize {
+    script[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(script.len(), |len| start + len)
+}
+
+/// Make sense of one line of the script.
+///
+/// Returns `Ok(None)` for blank lines and comments.
+fn parse_line(script: &[u8], start: usize, end: usize) -> Result<Option<Step>, ()> {
+    let line = &script[start..end];
+    if line.trim_ascii_start().starts_with(b"#") {
+        return Ok(None);
+    }
+    // Everything up to the first quote is words, and the rest is text
+    let quote = line.iter().position(|&b| b == b'"' || b == b'\'');
+    let (head, text) = match quote {
+        Some(quote) => {
+            let body = line[quote + 1..].trim_ascii_end();
+            let (&closing, body) = body.split_last().ok_or(())?;
+            if closing != line[quote] {
+                return Err(());
+            }
+            let text_start = start + quote + 1;
+            (&line[..quote], Some((text_start, text_start + body.len())))
+        }
+        None => (line, None),
+    };
+    let mut words = head
+        .split(u8::is_ascii_whitespace)
+        .filter(|w| !w.is_empty());
+    match words.next() {
+        None if text.is_none() => return Ok(None),
+        Some(b"at") => {}
+        _ => return Err(()),
+    }
+    let at_us = parse_time(words.next().ok_or(())?)?;
+    let action = match words.next().ok_or(())? {
+        b"type" => Action::Type,
+        b"send" => {
+            let name = words.next().ok_or(())?;
+            let device = SERIAL_NAMES
+                .iter()
+                .position(|n| n.as_bytes() == name)
+                .ok_or(())?;
+            Action::Send(device as u8)
+        }
+        b"press" => Action::Button(parse_index(words.next(), fpgaio::BUTTONS)?, true),
+        b"release" => Action::Button(parse_index(words.next(), fpgaio::BUTTONS)?, false),
+        b"switch" => {
+            let switch = parse_index(words.next(), fpgaio::SWITCHES)?;
+            match words.next() {
+                Some(b"on") => Action::Switch(switch, true),
+                Some(b"off") => Action::Switch(switch, false),
+                _ => return Err(()),
+            }
+        }
+        b"exit" => Action::Exit,
+        _ => return Err(()),
+    };
+    if words.next().is_some() {
+        return Err(());
+    }
+    let text = match (action, text) {
+        (Action::Type | Action::Send(_), Some((text_start, text_end))) => {
+            // Check the escapes now, so we don't have to worry about them later
+            let mut remaining = &script[text_start..text_end];
+            while !remaining.is_empty() {
+                let (_byte, used) = decode_byte(remaining).ok_or(())?;
+                remaining = &remaining[used..];
+            }
+            (text_start, text_end)
+        }
+        (Action::Type | Action::Send(_), None) => return Err(()),
+        (_, Some(_)) => return Err(()),
+        (_, None) => (end, end),
+    };
+    Ok(Some(Step {
+        at_us,
+        action,
+        text,
+    }))
+}
+
+/// Parse a time like `500ms`, into microseconds.
+fn parse_time(word: &[u8]) -> Result<u64, ()> {
+    let digits = word.iter().take_while(|b| b.is_ascii_digit()).count();
+    let scale = match &word[digits..] {
+        b"us" => 1,
+        b"ms" => 1_000,
+        b"s" => 1_000_000,
+        _ => return Err(()),
+    };
+    parse_number(&word[..digits])?.checked_mul(scale).ok_or(())
+}
+
+/// Parse a button or switch number, which must be less than `count`.
+fn parse_index(word: Option<&[u8]>, count: usize) -> Result<usize, ()> {
+    let index = parse_number(word.ok_or(())?)?;
+    if index < count as u64 {
+        Ok(index as usize)
+    } else {
+        Err(())
+    }
+}
+
+/// Parse a decimal number.
+fn parse_number(digits: &[u8]) -> Result<u64, ()> {
+    if digits.is_empty() {
+        return Err(());
+    }
+    digits.iter().try_fold(0u64, |total, &digit| {
+        if !digit.is_ascii_digit() {
+            return Err(());
+        }
+        total
+            .checked_mul(10)
+            .and_then(|total| total.checked_add(u64::from(digit - b'0')))
+            .ok_or(())
+    })
+}
+
+/// Get the first byte of some text, and how many bytes of text it took up.
+///
+/// Returns `None` if the text is empty or starts with a bad escape.
+fn decode_byte(text: &[u8]) -> Option<(u8, usize)> {
+    match text {
+        [b'\\', b'n', ..] => Some((b'\n', 2)),
+        [b'\\', b'r', ..] => Some((b'\r', 2)),
+        [b'\\', b't', ..] => Some((b'\t', 2)),
+        [b'\\', b'e', ..] => Some((0x1B, 2)),
+        [b'\\', b'x', high, low, ..] => {
+            let high = char::from(*high).to_digit(16)?;
+            let low = char::from(*low).to_digit(16)?;
+            Some((((high << 4) | low) as u8, 4))
+        }
+        [b'\\', escaped @ (b'\\' | b'\'' | b'"'), ..] => Some((*escaped, 2)),
+        [b'\\', ..] => None,
+        [byte, ..] => Some((*byte, 1)),
+        [] => None,
+    }
+}
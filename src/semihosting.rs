@@ -0,0 +1,152 @@
+//! Talking to the host through Arm semihosting.
+//!
+//! QEMU needs the `-semihosting` option for this to work. With a debugger
+//! attached, the debugger answers instead. If nothing answers, the call
+//! faults, and our HardFault handler (see `recover`) makes it fail instead.
+
+use neotron_common_bios as common;
+
+/// The instruction which makes a semihosting call
+const BKPT_SEMIHOSTING: u16 = 0xBEAB;
+
+/// Where the CPU stacks R0 in an exception frame, in words
+const FRAME_R0: usize = 0;
+
+/// Where the CPU stacks the PC in an exception frame, in words
+const FRAME_PC: usize = 6;
+
+/// Handle a HardFault, if it came from a semihosting call that nothing
+/// answered.
+///
+/// If so, we make the call return -1, which is how semihosting reports an
+/// error, and step over it. Returns `false` if the fault was something else.
+///
+/// # Safety
+///
+/// `frame` must point at the registers the CPU stacked for the HardFault.
+pub unsafe fn recover(frame: *mut u32) -> bool {
+    let pc = frame.add(FRAME_PC).read();
+    if (pc as *const u16).read_volatile() != BKPT_SEMIHOSTING {
+        return false;
+    }
+    frame.add(FRAME_R0).write(u32::MAX);
+    frame.add(FRAME_PC).write(pc + 2);
+    true
+}
+
+/// Ask the host what time it is, in seconds since the Unix epoch.
+///
+/// Returns `None` if the host wouldn't tell us, or isn't there.
+pub fn host_time() -> Option<u32> {
+    let secs = unsafe { cortex_m_semihosting::syscall!(TIME) };
+    if secs == 0 || secs == usize::MAX {
+        None
+    } else {
+        Some(secs as u32)
+    }
+}
+
+/// A file on the host.
+///
+/// The file is closed when this is dropped.
+pub struct HostFile {
+    handle: usize,
+}
+
+impl HostFile {
+    /// Open an existing file on the host, for reading.
+    ///
+    /// The path must end with a null byte.
+    #[cfg(any(feature = "host-config", feature = "script"))]
+    pub fn open_read(path: &[u8]) -> Option<HostFile> {
+        Self::open(path, cortex_m_semihosting::nr::open::R_BINARY)
+    }
+
+    /// Create a file on the host (or empty an existing one), for writing.
+    ///
+    /// The path must end with a null byte.
+    pub fn create(path: &[u8]) -> Option<HostFile> {
+        Self::open(path, cortex_m_semihosting::nr::open::W_TRUNC_BINARY)
+    }
+
+    /// Open a file with SYS_OPEN.
+    fn open(path: &[u8], mode: usize) -> Option<HostFile> {
+        let (_nul, name) = path.split_last()?;
+        let handle =
+            unsafe { cortex_m_semihosting::syscall!(OPEN, path.as_ptr(), mode, name.len()) };
+        if handle == usize::MAX {
+            None
+        } else {
+            Some(HostFile { handle })
+        }
+    }
+
+    /// Read as many bytes as we can into the buffer.
+    ///
+    /// Returns how many bytes were read.
+    #[cfg(any(feature = "host-config", feature = "script"))]
+    pub fn read(&mut self, buffer: &mut [u8]) -> usize {
+        // SYS_READ returns the number of bytes it *didn't* read
+        let not_read = unsafe {
+            cortex_m_semihosting::syscall!(READ, self.handle, buffer.as_mut_ptr(), buffer.len())
+        };
+        buffer.len().saturating_sub(not_read)
+    }
+
+    /// Write all of the bytes to the file.
+    ///
+    /// Returns `false` if the host couldn't write them all.
+    pub fn write_all(&mut self, data: &[u8]) -> bool {
+        // SYS_WRITE returns the number of bytes it *didn't* write
+        let not_written = unsafe {
+            cortex_m_semihosting::syscall!(WRITE, self.handle, data.as_ptr(), data.len())
+        };
+        not_written == 0
+    }
+}
+
+impl core::fmt::Write for HostFile {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.write_all(s.as_bytes()) {
+            Ok(())
+        } else {
+            Err(core::fmt::Error)
+        }
+    }
+}
+
+impl Drop for HostFile {
+    fn drop(&mut self) {
+        unsafe {
+            cortex_m_semihosting::syscall!(CLOSE, self.handle);
+        }
+    }
+}
+
+/// The semihosting console, as a serial port.
+pub struct Console;
+
+impl crate::SerialPort for Console {
+    fn configure(&mut self, _config: &common::serial::Config) -> Result<(), common::Error> {
+        // There's no baud rate on a semihosting console, so anything goes.
+        Ok(())
+    }
+
+    fn try_write(&mut self, byte: u8) -> bool {
+        // SYS_WRITEC always waits for the host to take the byte
+        unsafe {
+            cortex_m_semihosting::syscall!(WRITEC, &byte as *const u8);
+        }
+        true
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        // SYS_READC waits for the host to give us a byte
+        let byte = unsafe { cortex_m_semihosting::syscall!(READC) };
+        Some(byte as u8)
+    }
+
+    fn read_blocks(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,345 @@
+//! The BIOS setup menu.
+//!
+//! At boot we give you a moment to press F2 or Esc on the console. If you
+//! do, you get a menu for looking at the hardware and changing the BIOS
+//! settings, which are saved in the configuration store.
+
+use core::fmt::Write;
+
+use neotron_common_bios as common;
+
+use crate::{config, input, rtc, timer, Hardware, BIOS_VERSION, BLOCK_DEV_NAMES, SERIAL_NAMES};
+
+/// How long we wait for you to press F2 or Esc at boot
+const WINDOW_US: u64 = 1_000_000;
+
+/// How long we wait for the next byte of an escape sequence
+const ESCAPE_SEQUENCE_US: u64 = 50_000;
+
+/// Sent by the Esc key, and at the start of the sequence F2 sends
+const ESC: u8 = 0x1B;
+
+/// The baud rates you can pick for the console
+const BAUD_RATES: [u32; 8] = [
+    9600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+];
+
+/// Give you a moment to press F2 or Esc, and run the setup menu if you do.
+///
+/// We can't poll the semihosting console, so if that's our console we don't
+/// wait.
+pub fn offer(hw: &mut Hardware) {
+    if hw.console_port().read_blocks() {
+        return;
+    }
+    write!(hw.console(), "Press F2 or Esc to enter setup...\r\n").unwrap();
+    let end_us = timer::now_us() + WINDOW_US;
+    while timer::now_us() < end_us {
+        if hw.console_port().read() == Some(ESC) {
+            // Don't care whether it was Esc or F2
+            let _ = finish_escape(hw);
+            run(hw);
+            return;
+        }
+        cortex_m::asm::wfi();
+    }
+}
+
+/// Show the menu until you exit it.
+///
+/// Changes to the settings only take effect if you save them. Setting the
+/// clock takes effect immediately.
+fn run(hw: &mut Hardware) {
+    let mut settings = hw.settings.clone();
+    loop {
+        show_menu(hw, &settings);
+        match read_key(hw) {
+            b'1' => show_hardware(hw),
+            b'2' => set_clock(hw),
+            b'3' => {
+                settings.console_port = settings
+                    .console_port
+                    .checked_add(1)
+                    .filter(|&device| usize::from(device) < SERIAL_NAMES.len())
+                    .unwrap_or(0);
+            }
+            b'4' => {
+                let next = BAUD_RATES
+                    .iter()
+                    .position(|&baud| baud == settings.console_baud)
+                    .map_or(0, |idx| (idx + 1) % BAUD_RATES.len());
+                settings.console_baud = BAUD_RATES[next];
+            }
+            b'5' => settings.disk_write_protect = !settings.disk_write_protect,
+            b'6' => {
+                settings.clock_source = match settings.clock_source {
+                    config::ClockSource::Host => config::ClockSource::Rtc,
+                    config::ClockSource::Rtc => config::ClockSource::Host,
+                };
+            }
+            b'7' => {
+                let taken = [Some(settings.console_port), settings.keyboard_port];
+                settings.mirror_port = next_port(hw, settings.mirror_port, &taken, true);
+            }
+            b'8' => {
+                let taken = [Some(settings.console_port), settings.mirror_port];
+                settings.keyboard_port = next_port(hw, settings.keyboard_port, &taken, true);
+            }
+            b'9' => settings.debug_port = next_port(hw, settings.debug_port, &[], false),
+            b'n' | b'N' => edit_newline_modes(hw, &mut settings),
+            b's' | b'S' => {
+                save(hw, settings);
+                return;
+            }
+            b'q' | b'Q' => return,
+            ESC if finish_escape(hw) => return,
+            _ => {}
+        }
+    }
+}
+
+/// Print the main menu
+fn show_menu(hw: &mut Hardware, settings: &config::BiosSettings) {
+    let console_name = SERIAL_NAMES
+        .get(usize::from(settings.console_port))
+        .unwrap_or(&"?");
+    let write_protect = if settings.disk_write_protect {
+        "on"
+    } else {
+        "off"
+    };
+    let clock_source = match settings.clock_source {
+        config::ClockSource::Host => "host",
+        config::ClockSource::Rtc => "RTC",
+    };
+    let mirror_name = port_name(settings.mirror_port);
+    let keyboard_name = port_name(settings.keyboard_port);
+    let debug_name = port_name(settings.debug_port);
+    let mut console = hw.console();
+    write!(console, "\r\nNeotron BIOS Setup\r\n").unwrap();
+    write!(console, "==================\r\n").unwrap();
+    write!(console, " 1. Hardware information\r\n").unwrap();
+    write!(console, " 2. Set the date and time\r\n").unwrap();
+    write!(console, " 3. Console port      : {}\r\n", console_name).unwrap();
+    write!(
+        console,
+        " 4. Console baud rate : {}\r\n",
+        settings.console_baud
+    )
+    .unwrap();
+    write!(console, " 5. Disk write-protect: {}\r\n", write_protect).unwrap();
+    write!(console, " 6. Set clock from    : {}\r\n", clock_source).unwrap();
+    write!(console, " 7. Mirror screen to  : {}\r\n", mirror_name).unwrap();
+    write!(console, " 8. Keyboard from     : {}\r\n", keyboard_name).unwrap();
+    write!(console, " 9. Debug messages to : {}\r\n", debug_name).unwrap();
+    write!(console, " N. Line endings\r\n").unwrap();
+    write!(console, " S. Save and exit\r\n").unwrap();
+    write!(console, " Q. Exit without saving\r\n").unwrap();
+    write!(console, "Choice? ").unwrap();
+}
+
+/// Go through each serial port in turn, then off, skipping the ports in
+/// `taken`.
+///
+/// If we're going to poll the port, we also skip the ones we can't poll
+/// because their reads block.
+fn next_port(
+    hw: &mut Hardware,
+    port: Option<u8>,
+    taken: &[Option<u8>],
+    polled: bool,
+) -> Option<u8> {
+    let first = port.map_or(0, |device| device.saturating_add(1));
+    (first..SERIAL_NAMES.len() as u8).find(|&device| {
+        let blocks = hw
+            .serial_port(device)
+            .map_or(true, |port| port.read_blocks());
+        !taken.contains(&Some(device)) && !(polled && blocks)
+    })
+}
+
+/// The name of a serial port, or "off"
+fn port_name(port: Option<u8>) -> &'static str {
+    match port {
+        Some(device) => SERIAL_NAMES
+            .get(usize::from(device))
+            .copied()
+            .unwrap_or("?"),
+        None => "off",
+    }
+}
+
+/// Let you pick how each serial port translates line endings, until you go
+/// back to the main menu.
+fn edit_newline_modes(hw: &mut Hardware, settings: &mut config::BiosSettings) {
+    loop {
+        let mut console = hw.console();
+        write!(console, "\r\n\r\nLine endings\r\n").unwrap();
+        for (index, (name, mode)) in SERIAL_NAMES
+            .iter()
+            .zip(settings.newline_modes.iter())
+            .enumerate()
+        {
+            write!(console, " {}. {:<6}: {}\r\n", index + 1, name, mode.name()).unwrap();
+        }
+        write!(console, " Q. Back\r\n").unwrap();
+        write!(console, "Choice? ").unwrap();
+        match read_key(hw) {
+            key @ b'1'..=b'9' if usize::from(key - b'1') < settings.newline_modes.len() => {
+                let mode = &mut settings.newline_modes[usize::from(key - b'1')];
+                *mode = mode.next();
+            }
+            b'q' | b'Q' => return,
+            ESC if finish_escape(hw) => return,
+            _ => {}
+        }
+    }
+}
+
+/// Print what we know about the hardware, and wait for a key
+fn show_hardware(hw: &mut Hardware) {
+    let now = rtc::Timestamp(hw.rtc.get_time());
+    let uptime_secs = timer::now_us() / timer::TICKS_PER_SECOND;
+    let disk_mib = unsafe { crate::DISK_IMAGE.len() } / (1024 * 1024);
+    let mut console = hw.console();
+    write!(
+        console,
+        "\r\n\r\nBIOS      : Neotron QEMU BIOS {}\r\n",
+        BIOS_VERSION
+    )
+    .unwrap();
+    write!(console, "Machine   : Arm MPS3-AN547 (Cortex-M55)\r\n").unwrap();
+    write!(console, "Time      : {}\r\n", now).unwrap();
+    write!(console, "Uptime    : {} s\r\n", uptime_secs).unwrap();
+    for (device, name) in SERIAL_NAMES.iter().enumerate() {
+        let baud = hw.serial_config(device as u8).map(|c| c.data_rate_bps);
+        let mut console = hw.console();
+        match baud {
+            Some(baud) => write!(console, "{:<10}: {} baud\r\n", name, baud).unwrap(),
+            None => write!(console, "{:<10}: not configured\r\n", name).unwrap(),
+        }
+    }
+    let mut console = hw.console();
+    write!(
+        console,
+        "{:<10}: {} MiB disk\r\n",
+        BLOCK_DEV_NAMES[0], disk_mib
+    )
+    .unwrap();
+    write!(console, "Press any key...").unwrap();
+    let _ = read_key(hw);
+}
+
+/// Ask for the date and time, and set the RTC
+fn set_clock(hw: &mut Hardware) {
+    write!(
+        hw.console(),
+        "\r\n\r\nEnter the date and time (UTC) as YYYY-MM-DD HH:MM:SS, or Esc to cancel\r\n> "
+    )
+    .unwrap();
+    let mut buffer = [0u8; 32];
+    let Some(line) = read_line(hw, &mut buffer) else {
+        return;
+    };
+    match parse_time(line) {
+        Some(time) => {
+            hw.rtc.set_time(time);
+            let now = rtc::Timestamp(hw.rtc.get_time());
+            write!(hw.console(), "\r\nClock set to {}\r\n", now).unwrap();
+        }
+        None => write!(hw.console(), "\r\nThat's not a date and time I can use\r\n").unwrap(),
+    }
+}
+
+/// Save the settings, and start using them.
+///
+/// If the keyboard port has changed, we tell the terminal on the old one to
+/// stop reporting the mouse.
+fn save(hw: &mut Hardware, settings: config::BiosSettings) {
+    match config::save_bios_settings(&settings) {
+        Ok(()) => write!(hw.console(), "\r\nSettings saved\r\n").unwrap(),
+        Err(e) => write!(hw.console(), "\r\nCouldn't save settings: {:?}\r\n", e).unwrap(),
+    }
+    let console_config = common::serial::Config {
+        data_rate_bps: settings.console_baud,
+        ..crate::DEFAULT_SERIAL_CONFIG
+    };
+    let console_port = settings.console_port;
+    let old_keyboard_port = hw.settings.keyboard_port;
+    if settings.keyboard_port != old_keyboard_port {
+        if let Some(port) = old_keyboard_port.and_then(|device| hw.serial_port(device)) {
+            input::release(port);
+        }
+    }
+    hw.settings = settings;
+    if let Err(e) = hw.serial_configure(console_port, console_config) {
+        write!(hw.console(), "Couldn't configure console: {:?}\r\n", e).unwrap();
+    }
+}
+
+/// Parse a time like `2023-04-01 12:34:56`
+fn parse_time(line: &str) -> Option<common::Time> {
+    let mut fields = line
+        .trim()
+        .split(|c: char| matches!(c, '-' | ' ' | 'T' | ':'));
+    let mut next = || fields.next()?.parse::<u32>().ok();
+    let (year, month, day) = (next()?, next()?, next()?);
+    let (hour, minute, second) = (next()?, next()?, next()?);
+    if fields.next().is_some() {
+        return None;
+    }
+    rtc::time_from_civil(year, month, day, hour, minute, second)
+}
+
+/// Read a line of text from the console, echoing it back as it's typed.
+///
+/// Returns `None` if you press Esc.
+fn read_line<'a>(hw: &mut Hardware, buffer: &'a mut [u8]) -> Option<&'a str> {
+    let mut len = 0;
+    loop {
+        match read_key(hw) {
+            b'\r' | b'\n' => break,
+            ESC => {
+                let _ = finish_escape(hw);
+                return None;
+            }
+            0x08 | 0x7F if len > 0 => {
+                len -= 1;
+                write!(hw.console(), "\x08 \x08").unwrap();
+            }
+            byte @ 0x20..=0x7E if len < buffer.len() => {
+                buffer[len] = byte;
+                len += 1;
+                write!(hw.console(), "{}", byte as char).unwrap();
+            }
+            _ => {}
+        }
+    }
+    core::str::from_utf8(&buffer[..len]).ok()
+}
+
+/// Wait for a byte from the console
+fn read_key(hw: &mut Hardware) -> u8 {
+    loop {
+        if let Some(byte) = hw.console_port().read() {
+            return byte;
+        }
+        cortex_m::asm::wfi();
+    }
+}
+
+/// After an Esc, throw away the rest of any escape sequence (like the one
+/// F2 or the arrow keys send).
+///
+/// Returns `true` if it was just the Esc key.
+fn finish_escape(hw: &mut Hardware) -> bool {
+    let mut just_esc = true;
+    let mut end_us = timer::now_us() + ESCAPE_SEQUENCE_US;
+    while timer::now_us() < end_us {
+        if hw.console_port().read().is_some() {
+            just_esc = false;
+            end_us = timer::now_us() + ESCAPE_SEQUENCE_US;
+        }
+    }
+    just_esc
+}
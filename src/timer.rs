@@ -0,0 +1,84 @@
+//! A 64-bit microsecond counter, driven by the SysTick timer.
+//!
+//! SysTick is only 24 bits wide, so we have it interrupt us once a
+//! millisecond and count the interrupts. The microseconds come from how far
+//! SysTick has counted down since the last interrupt.
+
+use core::cell::Cell;
+
+use cortex_m::peripheral::{syst::SystClkSource, SCB, SYST};
+use neotron_common_bios as common;
+
+/// The clock speed of the Cortex-M55 on the MPS3-AN547.
+///
+/// The SSE-300 doesn't give SysTick a reference clock, so we count CPU
+/// clock cycles.
+const CPU_CLOCK: u32 = 32_000_000;
+
+/// How many SysTick interrupts we take per second
+const TICK_RATE_HZ: u32 = 1000;
+
+/// What we load into SysTick, so it wraps once a millisecond
+const RELOAD: u32 = (CPU_CLOCK / TICK_RATE_HZ) - 1;
+
+/// How many CPU clock cycles in a microsecond
+const CYCLES_PER_US: u32 = CPU_CLOCK / 1_000_000;
+
+/// The rate of the counter returned by `now_us`
+pub const TICKS_PER_SECOND: u64 = 1_000_000;
+
+/// Counts SysTick interrupts.
+static MILLISECONDS: critical_section::Mutex<Cell<u64>> =
+    critical_section::Mutex::new(Cell::new(0));
+
+/// Start SysTick interrupting us once a millisecond.
+pub fn init(syst: &mut SYST) {
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(RELOAD);
+    syst.clear_current();
+    syst.enable_interrupt();
+    syst.enable_counter();
+}
+
+/// Call this from the SysTick exception handler.
+pub fn tick() {
+    critical_section::with(|cs| {
+        let ms = MILLISECONDS.borrow(cs);
+        ms.set(ms.get() + 1);
+    });
+}
+
+/// How many microseconds have passed since the timer was started.
+pub fn now_us() -> u64 {
+    critical_section::with(|cs| {
+        let mut ms = MILLISECONDS.borrow(cs).get();
+        let mut current = SYST::get_current();
+        if SCB::is_pendst_pending() {
+            // SysTick has wrapped, but we've stopped the interrupt from
+            // counting it.
+            ms += 1;
+            current = SYST::get_current();
+        }
+        let us_this_ms = (RELOAD - current) / CYCLES_PER_US;
+        (ms * 1000) + u64::from(us_this_ms)
+    })
+}
+
+/// A point in time, after which we stop waiting for something.
+pub struct Deadline {
+    expires_us: u64,
+}
+
+impl Deadline {
+    /// Create a deadline which expires after the given timeout.
+    pub fn new(timeout: common::Timeout) -> Deadline {
+        Deadline {
+            expires_us: now_us() + (u64::from(timeout.get_ms()) * 1000),
+        }
+    }
+
+    /// Has the deadline expired?
+    pub fn has_passed(&self) -> bool {
+        now_us() >= self.expires_us
+    }
+}
@@ -0,0 +1,342 @@
+//! A driver for the CMSDK UART.
+//!
+//! Received bytes are moved into a ring buffer by the RX interrupt handler,
+//! and bytes to be sent are taken from a ring buffer by the TX interrupt
+//! handler. Thread-mode code only ever talks to the ring buffers.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use neotron_common_bios as common;
+
+use crate::ring::RingBuffer;
+
+/// How many bytes we buffer in each direction
+const BUFFER_LEN: usize = 256;
+
+/// `DeviceError` code: the UART received a byte before we had taken the last
+/// one, so the hardware dropped it.
+pub const ERROR_RX_OVERRUN: u16 = 1;
+
+/// `DeviceError` code: we took bytes from the UART but had nowhere to put
+/// them, because the OS wasn't reading fast enough.
+pub const ERROR_RX_BUFFER_FULL: u16 = 2;
+
+/// Counts of the things that have gone wrong on a UART.
+///
+/// The CMSDK UART can't detect framing errors or breaks, so we can only count
+/// overruns.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineErrors {
+    /// Bytes the hardware dropped on receive
+    pub rx_overruns: u32,
+    /// Bytes we dropped on receive because our buffer was full
+    pub rx_dropped: u32,
+}
+
+/// The buffers shared between a UART's interrupt handler and thread-mode
+/// code.
+pub struct UartState {
+    /// Bytes received, waiting for the OS to read them
+    rx: RingBuffer<u8, BUFFER_LEN>,
+    /// Bytes from the OS, waiting to be transmitted
+    tx: RingBuffer<u8, BUFFER_LEN>,
+    /// How many received bytes we dropped because `rx` was full. Only the
+    /// interrupt handler writes to this.
+    rx_dropped: AtomicU32,
+    /// How many RX overruns the hardware reported. Only the interrupt handler
+    /// writes to this.
+    rx_overruns: AtomicU32,
+    /// The error counts when we last told the OS about them. Only thread-mode
+    /// code touches this.
+    reported: core::cell::Cell<LineErrors>,
+}
+
+// Safety: `reported` is only accessed from thread mode
+unsafe impl Sync for UartState {}
+
+impl UartState {
+    /// Create some empty UART buffers.
+    const fn new() -> UartState {
+        UartState {
+            rx: RingBuffer::new(0),
+            tx: RingBuffer::new(0),
+            rx_dropped: AtomicU32::new(0),
+            rx_overruns: AtomicU32::new(0),
+            reported: core::cell::Cell::new(LineErrors {
+                rx_overruns: 0,
+                rx_dropped: 0,
+            }),
+        }
+    }
+
+    /// Add one to a counter which only the interrupt handler writes to.
+    fn increment(counter: &AtomicU32) {
+        let value = counter.load(Ordering::Relaxed);
+        counter.store(value.wrapping_add(1), Ordering::Relaxed);
+    }
+}
+
+/// The buffers for UART0 to UART5
+pub static UART_STATES: [UartState; 6] = {
+    const EMPTY: UartState = UartState::new();
+    [EMPTY; 6]
+};
+
+/// A driver for CMSDK Uart
+pub struct Uart<const ADDR: usize> {
+    state: &'static UartState,
+}
+
+impl<const ADDR: usize> Uart<ADDR> {
+    const STATUS_TX_FULL: u32 = 1 << 0;
+    const STATUS_RX_NON_EMPTY: u32 = 1 << 1;
+    const STATUS_TX_OVERRUN: u32 = 1 << 2;
+    const STATUS_RX_OVERRUN: u32 = 1 << 3;
+
+    const CONTROL_TX_EN: u32 = 1 << 0;
+    const CONTROL_RX_EN: u32 = 1 << 1;
+    const CONTROL_TX_INT_EN: u32 = 1 << 2;
+    const CONTROL_RX_INT_EN: u32 = 1 << 3;
+    const CONTROL_TX_OVERRUN_INT_EN: u32 = 1 << 4;
+    const CONTROL_RX_OVERRUN_INT_EN: u32 = 1 << 5;
+
+    const INT_TX: u32 = 1 << 0;
+    const INT_RX: u32 = 1 << 1;
+    const INT_TX_OVERRUN: u32 = 1 << 2;
+    const INT_RX_OVERRUN: u32 = 1 << 3;
+
+    /// The hardware can't go faster than 1/16th of the clock
+    const MIN_BAUDDIV: u32 = 16;
+    /// The baud rate divider register is 20 bits wide
+    const MAX_BAUDDIV: u32 = (1 << 20) - 1;
+    /// How far off (in parts per thousand) we can be from the requested baud
+    /// rate and still expect the other end to understand us.
+    const MAX_BAUD_ERROR_PPT: u32 = 20;
+
+    /// Create a handle to the UART at `ADDR`, which uses the given buffers.
+    ///
+    /// Every handle for the same UART must use the same buffers.
+    pub const fn new(state: &'static UartState) -> Uart<ADDR> {
+        Uart { state }
+    }
+
+    /// Work out the baud rate divider for the given configuration.
+    ///
+    /// The CMSDK UART only does 8 data bits, no parity and one stop bit, and
+    /// has no handshaking lines, so anything else is an error. So is a baud
+    /// rate we can't get reasonably close to.
+    pub fn divider_for(
+        config: &common::serial::Config,
+        system_clock: u32,
+    ) -> Result<u32, common::Error> {
+        if config.data_bits != common::serial::DataBits::Eight
+            || config.parity != common::serial::Parity::None
+            || config.stop_bits != common::serial::StopBits::One
+            || config.handshaking != common::serial::Handshaking::None
+        {
+            return Err(common::Error::UnsupportedConfiguration(0));
+        }
+        let baudrate = config.data_rate_bps;
+        if baudrate == 0 {
+            return Err(common::Error::UnsupportedConfiguration(0));
+        }
+        // Round to the nearest divider
+        let divider = (system_clock + (baudrate / 2)) / baudrate;
+        if !(Self::MIN_BAUDDIV..=Self::MAX_BAUDDIV).contains(&divider) {
+            return Err(common::Error::UnsupportedConfiguration(0));
+        }
+        let actual = system_clock / divider;
+        let error_ppt = (u64::from(actual.abs_diff(baudrate)) * 1000) / u64::from(baudrate);
+        if error_ppt > u64::from(Self::MAX_BAUD_ERROR_PPT) {
+            return Err(common::Error::UnsupportedConfiguration(0));
+        }
+        Ok(divider)
+    }
+
+    /// Turn on TX and RX, and their interrupts (including the overrun ones,
+    /// so we hear about an overrun as soon as it happens)
+    pub fn enable(&mut self, divider: u32) {
+        self.set_bauddiv(divider);
+        self.set_control(
+            Self::CONTROL_TX_EN
+                | Self::CONTROL_RX_EN
+                | Self::CONTROL_TX_INT_EN
+                | Self::CONTROL_RX_INT_EN
+                | Self::CONTROL_TX_OVERRUN_INT_EN
+                | Self::CONTROL_RX_OVERRUN_INT_EN,
+        );
+    }
+
+    /// Queue a byte for transmission (blocking if there's no space)
+    pub fn write(&mut self, byte: u8) {
+        while !self.try_write(byte) {}
+    }
+
+    /// Try and queue a byte for transmission.
+    ///
+    /// Returns `false` if there was no space.
+    pub fn try_write(&mut self, byte: u8) -> bool {
+        let queued = self.state.tx.push(byte).is_ok();
+        // The TX interrupt will make space, but we also keep the hardware
+        // topped up ourselves in case interrupts are off.
+        self.start_tx();
+        queued
+    }
+
+    /// Try and read a byte from the receive buffer
+    pub fn read(&mut self) -> Option<u8> {
+        self.state.rx.pop()
+    }
+
+    /// Get the error counts for this UART
+    pub fn line_errors(&self) -> LineErrors {
+        LineErrors {
+            rx_overruns: self.state.rx_overruns.load(Ordering::Relaxed),
+            rx_dropped: self.state.rx_dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Find out if we have lost any received bytes since the last time this
+    /// was called.
+    pub fn take_error(&mut self) -> Option<common::Error> {
+        let current = self.line_errors();
+        let reported = self.state.reported.replace(current);
+        if current.rx_overruns != reported.rx_overruns {
+            Some(common::Error::DeviceError(ERROR_RX_OVERRUN))
+        } else if current.rx_dropped != reported.rx_dropped {
+            Some(common::Error::DeviceError(ERROR_RX_BUFFER_FULL))
+        } else {
+            None
+        }
+    }
+
+    /// Wait until everything in the transmit buffer has been handed to the
+    /// hardware.
+    pub fn flush(&mut self) {
+        while !self.state.tx.is_empty() {
+            self.start_tx();
+        }
+    }
+
+    /// Handle the RX and TX interrupts for this UART.
+    ///
+    /// Call this from the interrupt handler (or, on Armv6-M, from SysTick,
+    /// whether the UART has interrupted or not).
+    pub fn interrupt(&mut self) {
+        let int_status = self.get_int_status();
+        self.set_int_clear(
+            int_status
+                & (Self::INT_TX | Self::INT_RX | Self::INT_TX_OVERRUN | Self::INT_RX_OVERRUN),
+        );
+        // The overrun bits are sticky until we write a 1 to them. We never
+        // write to the hardware when it's full, so a TX overrun can't lose
+        // anything of ours; we just clear it.
+        let status = self.get_status();
+        if (status & Self::STATUS_RX_OVERRUN) != 0 {
+            UartState::increment(&self.state.rx_overruns);
+        }
+        self.set_status(status & (Self::STATUS_RX_OVERRUN | Self::STATUS_TX_OVERRUN));
+        while (self.get_status() & Self::STATUS_RX_NON_EMPTY) != 0 {
+            let byte = self.get_data() as u8;
+            if self.state.rx.push(byte).is_err() {
+                UartState::increment(&self.state.rx_dropped);
+            }
+        }
+        self.fill_tx();
+    }
+
+    /// Move bytes from the TX buffer to the hardware, with interrupts off.
+    ///
+    /// The TX interrupt handler also takes bytes from the TX buffer, so we
+    /// must not be interrupted half way through.
+    fn start_tx(&mut self) {
+        critical_section::with(|_| self.fill_tx());
+    }
+
+    /// Move bytes from the TX buffer into the hardware until it is full.
+    fn fill_tx(&mut self) {
+        while (self.get_status() & Self::STATUS_TX_FULL) == 0 {
+            let Some(byte) = self.state.tx.pop() else {
+                break;
+            };
+            self.set_data(byte as u32);
+        }
+    }
+
+    /// Read the data register
+    fn get_data(&mut self) -> u32 {
+        let ptr = ADDR as *mut u32;
+        unsafe { ptr.read_volatile() }
+    }
+
+    /// Write the data register
+    fn set_data(&mut self, data: u32) {
+        let ptr = ADDR as *mut u32;
+        unsafe { ptr.write_volatile(data) }
+    }
+
+    /// Read the status register
+    fn get_status(&self) -> u32 {
+        let ptr = (ADDR + 4) as *mut u32;
+        unsafe { ptr.read_volatile() }
+    }
+
+    /// Write the status register, to clear the overrun bits
+    fn set_status(&mut self, data: u32) {
+        let ptr = (ADDR + 4) as *mut u32;
+        unsafe { ptr.write_volatile(data) }
+    }
+
+    /// Set the control register
+    fn set_control(&mut self, data: u32) {
+        let ptr = (ADDR + 8) as *mut u32;
+        unsafe { ptr.write_volatile(data) }
+    }
+
+    /// Read the interrupt status register
+    fn get_int_status(&self) -> u32 {
+        let ptr = (ADDR + 12) as *mut u32;
+        unsafe { ptr.read_volatile() }
+    }
+
+    /// Write the interrupt clear register
+    fn set_int_clear(&mut self, data: u32) {
+        let ptr = (ADDR + 12) as *mut u32;
+        unsafe { ptr.write_volatile(data) }
+    }
+
+    /// Set the baud rate divider register
+    fn set_bauddiv(&mut self, data: u32) {
+        let ptr = (ADDR + 16) as *mut u32;
+        unsafe { ptr.write_volatile(data) }
+    }
+}
+
+impl<const ADDR: usize> crate::SerialPort for Uart<ADDR> {
+    fn configure(&mut self, config: &common::serial::Config) -> Result<(), common::Error> {
+        let divider = Self::divider_for(config, crate::PERIPHERAL_CLOCK)?;
+        self.enable(divider);
+        Ok(())
+    }
+
+    fn try_write(&mut self, byte: u8) -> bool {
+        Uart::try_write(self, byte)
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        Uart::read(self)
+    }
+
+    fn take_error(&mut self) -> Option<common::Error> {
+        Uart::take_error(self)
+    }
+}
+
+impl<const N: usize> core::fmt::Write for Uart<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for b in s.bytes() {
+            self.write(b);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+//! Video modes, and the memory the picture lives in.
+//!
+//! There's no display on the MPS3-AN547 (or at least, not one QEMU emulates),
+//! so we just keep track of the mode and give the OS somewhere to draw. The
+//! `mirror` module shows text modes on a serial port, and the `screenshot`
+//! module can save any mode to a file. We also pretend to be generating a
+//! video signal, so `video_wait_for_line` waits as long as it would on real
+//! hardware.
+//!
+//! The text modes live in our own VRAM. The graphics modes live in a buffer
+//! in the FPGA SRAM if they fit, otherwise the OS has to give us some memory
+//! with `video_set_framebuffer`. The OS can give us memory for any mode if
+//! it wants to.
+
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU8, Ordering};
+
+use neotron_common_bios as common;
+
+use common::video::{Format, Mode, Timing};
+
+use crate::timer;
+
+/// The most text cells we can hold. This is enough for 80x60, the biggest
+/// of the 640 pixel wide text modes.
+pub const MAX_TEXT_CELLS: usize = 80 * 60;
+
+/// Where the OS can put the text characters, and their attributes
+static mut VRAM: [(u8, u8); MAX_TEXT_CELLS] = [(0, 0); MAX_TEXT_CELLS];
+
+/// How many bytes of pixels we can hold. This is enough for 640x480 at 8
+/// bits per pixel.
+const GRAPHICS_RAM_LEN: usize = 640 * 480;
+
+/// Where the OS can draw in graphics modes.
+///
+/// This is too big for the BIOS RAM, so it goes in the FPGA SRAM.
+#[link_section = ".fpga_sram"]
+static mut GRAPHICS_RAM: [u8; GRAPHICS_RAM_LEN] = [0; GRAPHICS_RAM_LEN];
+
+/// Memory the OS has given us to use for the current mode, or null if we're
+/// using our own
+static OS_FRAMEBUFFER: AtomicPtr<u8> = AtomicPtr::new(core::ptr::null_mut());
+
+/// The mode we are in, as a `u8`.
+///
+/// Mode 0 (80x30 text, at 640x480) is the one we start in.
+static CURRENT_MODE: AtomicU8 = AtomicU8::new(0);
+
+/// The palette, with each colour packed as `0x00RRGGBB`.
+///
+/// The text modes use the first 16 entries.
+static PALETTE: [AtomicU32; 256] = {
+    const BLACK: AtomicU32 = AtomicU32::new(0);
+    let mut palette = [BLACK; 256];
+    let mut index = 0;
+    while index < palette.len() {
+        palette[index] = AtomicU32::new(default_colour(index));
+        index += 1;
+    }
+    palette
+};
+
+/// Goes up every time one of the text-mode colours changes while we're in a
+/// text mode, so anything drawing text knows to start again
+static PALETTE_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// Can we do this mode?
+///
+/// We do any text mode that fits in our VRAM, and the graphics modes which
+/// use the palette (those with 8 bits per pixel or fewer).
+pub fn is_valid_mode(mode: Mode) -> bool {
+    match mode.format() {
+        Format::Text8x16 | Format::Text8x8 => {
+            mode.frame_size_bytes() <= core::mem::size_of::<[(u8, u8); MAX_TEXT_CELLS]>()
+        }
+        Format::Chunky1 | Format::Chunky2 | Format::Chunky4 | Format::Chunky8 => true,
+        _ => false,
+    }
+}
+
+/// Does the OS have to give us memory before we can show this mode?
+pub fn mode_needs_vram(mode: Mode) -> bool {
+    is_valid_mode(mode) && our_framebuffer(mode).is_null()
+}
+
+/// Switch to a new mode, if we can do it.
+///
+/// This forgets any memory the OS gave us for the old mode.
+pub fn set_mode(mode: Mode) -> Result<(), common::Error> {
+    if !is_valid_mode(mode) {
+        return Err(common::Error::UnsupportedConfiguration(0));
+    }
+    OS_FRAMEBUFFER.store(core::ptr::null_mut(), Ordering::Relaxed);
+    CURRENT_MODE.store(mode.as_u8(), Ordering::Relaxed);
+    Ok(())
+}
+
+/// Get the mode we are in.
+pub fn mode() -> Mode {
+    // We only ever store modes that `is_valid_mode` was happy with
+    unsafe { Mode::from_u8(CURRENT_MODE.load(Ordering::Relaxed)) }
+}
+
+/// Use some memory the OS has given us for the current mode.
+///
+/// # Safety
+///
+/// The memory must be at least `frame_size_bytes()` long for the current
+/// mode, and stay valid until the mode changes.
+pub unsafe fn set_framebuffer(buffer: *mut u8) -> Result<(), common::Error> {
+    if buffer.is_null() {
+        return Err(common::Error::UnsupportedConfiguration(0));
+    }
+    OS_FRAMEBUFFER.store(buffer, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Get the start of the memory the current mode is drawn from.
+///
+/// This is null if the mode needs memory from the OS and it hasn't given us
+/// any yet.
+pub fn framebuffer() -> *mut u8 {
+    let os_framebuffer = OS_FRAMEBUFFER.load(Ordering::Relaxed);
+    if os_framebuffer.is_null() {
+        our_framebuffer(mode())
+    } else {
+        os_framebuffer
+    }
+}
+
+/// Get our own memory for a mode, or null if the mode doesn't fit.
+fn our_framebuffer(mode: Mode) -> *mut u8 {
+    match mode.format() {
+        Format::Text8x16 | Format::Text8x8 => unsafe { core::ptr::addr_of_mut!(VRAM) as *mut u8 },
+        _ if mode.frame_size_bytes() <= GRAPHICS_RAM_LEN => unsafe {
+            core::ptr::addr_of_mut!(GRAPHICS_RAM) as *mut u8
+        },
+        _ => core::ptr::null_mut(),
+    }
+}
+
+/// Wait until an imaginary video signal for the current mode reaches the
+/// start of the given line.
+///
+/// Lines past the bottom of the screen wait for the end of the last visible
+/// line. If we're already at the start of the line (to the nearest
+/// microsecond, which is as well as we can tell), we wait for it to come round
+/// again, so calling this in a loop waits a frame each time.
+pub fn wait_for_line(line: u16) {
+    let mode = mode();
+    let raster = Raster::new(mode.timing());
+    // Some modes draw each line twice
+    let mode_lines = u64::from(mode.vertical_lines().max(1));
+    let lines_per_mode_line = (raster.visible_lines / mode_lines).max(1);
+    let target = (u64::from(line) * lines_per_mode_line).min(raster.visible_lines);
+
+    let now_ns = timer::now_us() * 1000;
+    let frame_ns = raster.line_ns * raster.total_lines;
+    let position_ns = now_ns % frame_ns;
+    let target_ns = target * raster.line_ns;
+    let mut wait_ns = (target_ns + frame_ns - position_ns) % frame_ns;
+    if wait_ns < 1000 {
+        wait_ns += frame_ns;
+    }
+    let until_us = (now_ns + wait_ns + 999) / 1000;
+    loop {
+        let now_us = timer::now_us();
+        if now_us >= until_us {
+            break;
+        }
+        if until_us - now_us > 1000 {
+            // Sleep until the next tick (at most a millisecond)
+            cortex_m::asm::wfi();
+        }
+    }
+}
+
+/// The shape of the video signal for one of the timings
+struct Raster {
+    /// How many lines have pixels on
+    visible_lines: u64,
+    /// How many lines in each frame, including the blanking
+    total_lines: u64,
+    /// How long it takes to draw each line, in nanoseconds
+    line_ns: u64,
+}
+
+impl Raster {
+    /// Get the standard VESA timing
+    fn new(timing: Timing) -> Raster {
+        let (visible_lines, total_lines, refresh_hz) = match timing {
+            Timing::T640x480 => (480, 525, 60),
+            Timing::T640x400 => (400, 449, 70),
+            Timing::T800x600 => (600, 628, 60),
+        };
+        Raster {
+            visible_lines,
+            total_lines,
+            line_ns: 1_000_000_000 / (refresh_hz * total_lines),
+        }
+    }
+}
+
+/// Read a text cell from the framebuffer, as a (glyph, attribute) pair.
+///
+/// Returns `None` if we have no framebuffer, or the cell is off the end of
+/// it. The OS writes to the framebuffer through a raw pointer, so we read it
+/// with a volatile read.
+pub fn text_cell(index: usize) -> Option<(u8, u8)> {
+    let framebuffer = framebuffer();
+    if framebuffer.is_null() || ((index + 1) * 2) > mode().frame_size_bytes() {
+        return None;
+    }
+    Some(unsafe { framebuffer.cast::<(u8, u8)>().add(index).read_volatile() })
+}
+
+/// Read a pixel from the framebuffer, as a palette index.
+///
+/// Returns `None` if we're not in a graphics mode, we have no framebuffer,
+/// or the pixel is off the screen. With fewer than 8 bits per pixel, the
+/// left-most pixel is in the most significant bits of each byte.
+pub fn pixel(x: usize, y: usize) -> Option<u8> {
+    let mode = mode();
+    let bits_per_pixel = match mode.format() {
+        Format::Chunky1 => 1,
+        Format::Chunky2 => 2,
+        Format::Chunky4 => 4,
+        Format::Chunky8 => 8,
+        _ => return None,
+    };
+    let width = usize::from(mode.horizontal_pixels());
+    if x >= width || y >= usize::from(mode.vertical_lines()) {
+        return None;
+    }
+    let framebuffer = framebuffer();
+    if framebuffer.is_null() {
+        return None;
+    }
+    let bit_offset = ((y * width) + x) * bits_per_pixel;
+    let byte = unsafe { framebuffer.add(bit_offset / 8).read_volatile() };
+    let shift = 8 - bits_per_pixel - (bit_offset % 8);
+    Some((byte >> shift) & (0xFF >> (8 - bits_per_pixel)))
+}
+
+/// Get the colour of one of the 16 text-mode colours, as red, green and blue.
+pub fn text_colour(index: u8) -> [u8; 3] {
+    palette_entry(index & 0x0F)
+}
+
+/// Get a colour from the palette, as red, green and blue.
+pub fn palette_entry(index: u8) -> [u8; 3] {
+    let [_, red, green, blue] = PALETTE[usize::from(index)]
+        .load(Ordering::Relaxed)
+        .to_be_bytes();
+    [red, green, blue]
+}
+
+/// Change a colour in the palette.
+///
+/// Only call this from thread mode.
+pub fn set_palette_entry(index: u8, [red, green, blue]: [u8; 3]) {
+    PALETTE[usize::from(index)].store(u32::from_be_bytes([0, red, green, blue]), Ordering::Relaxed);
+    if index >= 16 || mode().text_width().is_none() {
+        // Nothing is drawing text with this colour
+        return;
+    }
+    // We have no compare-and-swap, so we can't use `fetch_add`. That's OK,
+    // because nothing else changes the palette.
+    let generation = PALETTE_GENERATION.load(Ordering::Relaxed);
+    PALETTE_GENERATION.store(generation.wrapping_add(1), Ordering::Relaxed);
+}
+
+/// Find out which version of the palette we have.
+///
+/// If this number changes, so has the palette.
+pub fn palette_generation() -> u32 {
+    PALETTE_GENERATION.load(Ordering::Relaxed)
+}
+
+/// Work out the colour a palette entry starts as.
+///
+/// This is the palette a VGA card has in mode 13h: the 16 text-mode colours,
+/// then 16 shades of grey, then a rainbow of 24 hues in each of nine
+/// brightness and saturation ramps, then eight blacks. VGA has six bits per
+/// channel, so we stretch them to eight.
+const fn default_colour(index: usize) -> u32 {
+    /// The 16 shades of grey, in VGA's six-bit levels
+    const GREYS: [u8; 16] = [0, 5, 8, 11, 14, 17, 20, 24, 28, 32, 36, 40, 45, 50, 56, 63];
+    /// The levels in each ramp, from the least to the most of a channel, in
+    /// VGA's six-bit levels
+    const RAMPS: [[u8; 5]; 9] = [
+        [0, 16, 31, 47, 63],
+        [31, 39, 47, 55, 63],
+        [45, 49, 54, 58, 63],
+        [0, 7, 14, 21, 28],
+        [14, 17, 21, 24, 28],
+        [20, 22, 24, 26, 28],
+        [0, 4, 8, 12, 16],
+        [8, 10, 12, 14, 16],
+        [11, 12, 13, 15, 16],
+    ];
+    if index < 16 {
+        let [red, green, blue] = TEXT_COLOURS[index];
+        return u32::from_be_bytes([0, red, green, blue]);
+    }
+    let [red, green, blue] = if index < 32 {
+        let level = GREYS[index - 16];
+        [level, level, level]
+    } else if index < 248 {
+        let ramp = RAMPS[(index - 32) / 24];
+        // Go round from blue, through magenta, red, yellow, green and cyan
+        let hue = (index - 32) % 24;
+        let step = hue % 4;
+        let [red, green, blue] = match hue / 4 {
+            0 => [step, 0, 4],
+            1 => [4, 0, 4 - step],
+            2 => [4, step, 0],
+            3 => [4 - step, 4, 0],
+            4 => [0, 4, step],
+            _ => [0, 4 - step, 4],
+        };
+        [ramp[red], ramp[green], ramp[blue]]
+    } else {
+        [0, 0, 0]
+    };
+    u32::from_be_bytes([0, stretch(red), stretch(green), stretch(blue)])
+}
+
+/// Turn a six-bit VGA level into an eight-bit one
+const fn stretch(level: u8) -> u8 {
+    (level << 2) | (level >> 4)
+}
+
+/// The standard VGA text-mode colours
+const TEXT_COLOURS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], // Black
+    [0x00, 0x00, 0xAA], // Blue
+    [0x00, 0xAA, 0x00], // Green
+    [0x00, 0xAA, 0xAA], // Cyan
+    [0xAA, 0x00, 0x00], // Red
+    [0xAA, 0x00, 0xAA], // Magenta
+    [0xAA, 0x55, 0x00], // Brown
+    [0xAA, 0xAA, 0xAA], // Light Grey
+    [0x55, 0x55, 0x55], // Dark Grey
+    [0x55, 0x55, 0xFF], // Light Blue
+    [0x55, 0xFF, 0x55], // Light Green
+    [0x55, 0xFF, 0xFF], // Light Cyan
+    [0xFF, 0x55, 0x55], // Light Red
+    [0xFF, 0x55, 0xFF], // Pink
+    [0xFF, 0xFF, 0x55], // Yellow
+    [0xFF, 0xFF, 0xFF], // White
+];
+
+/// Convert a glyph from our font (which is Code Page 850) into Unicode.
+///
+/// Control characters get the symbols the IBM PC shows for them, except
+/// for NUL, which is blank.
+pub fn glyph_to_char(glyph: u8) -> char {
+    match glyph {
+        0x00..=0x1F => CP850_LOW[usize::from(glyph)],
+        0x7F => '⌂',
+        0x20..=0x7E => char::from(glyph),
+        0x80..=0xFF => CP850_HIGH[usize::from(glyph - 0x80)],
+    }
+}
+
+/// The glyphs for 0x00 to 0x1F
+static CP850_LOW: [char; 32] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', //
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼', //
+];
+
+/// The glyphs for 0x80 to 0xFF.
+///
+/// 0xF0 is a soft hyphen, which terminals don't draw, so we use a hyphen.
+static CP850_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '®', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À', '©', '╣', '║', '╗', '╝', '¢', '¥', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤', //
+    'ð', 'Ð', 'Ê', 'Ë', 'È', 'ı', 'Í', 'Î', 'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀', //
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ', 'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´', //
+    '-', '±', '‗', '¾', '¶', '§', '÷', '¸', '°', '¨', '·', '¹', '³', '²', '■', '\u{A0}', //
+];